@@ -0,0 +1,115 @@
+// compares the old search_insensitive (to_lowercase on every line) with the case folding one
+// on ASCII text and on multilingual text, and counts the allocations each of them makes
+// run with: cargo bench --bench casefold
+// the size of the text can be changed with MINIGREP_BENCH_MB (default 32)
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use minigrep::search_insensitive;
+
+// a global allocator that only counts how often alloc is called
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+// the implementation before case folding, kept here to compare against
+fn search_insensitive_lowercase<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    let query = query.to_lowercase();
+    let mut results = Vec::new();
+    for line in contents.lines() {
+        if line.to_lowercase().contains(&query) {
+            results.push(line);
+        }
+    }
+    results
+}
+
+// builds about size_mb of text by repeating lines, every 100th line contains the query
+fn text(lines: &[&str], hit: &str, size_mb: usize) -> String {
+    let mut text = String::new();
+    let mut i = 0usize;
+    while text.len() < size_mb * 1024 * 1024 {
+        let line = if i.is_multiple_of(100) {
+            hit
+        } else {
+            lines[i % lines.len()]
+        };
+        text.push_str(line);
+        text.push('\n');
+        i += 1;
+    }
+    text
+}
+
+fn run<'a>(name: &str, text: &'a str, search: impl Fn(&'a str) -> Vec<&'a str>) -> usize {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    let found = black_box(search(text)).len();
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+    println!(
+        "  {name:<12} {found:>8} lines, {:>8.1} MB/s, {allocations:>9} allocations",
+        text.len() as f64 / (1024.0 * 1024.0) / elapsed.as_secs_f64()
+    );
+    found
+}
+
+fn main() {
+    let size_mb: usize = std::env::var("MINIGREP_BENCH_MB")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(32);
+
+    let ascii = text(
+        &[
+            "2024-05-01 INFO request handled in 12 ms",
+            "2024-05-01 DEBUG cache miss for key user:1234",
+            "2024-05-01 WARN slow query took 250 ms",
+        ],
+        "2024-05-01 ERROR Connection Timeout while talking to upstream",
+        size_mb,
+    );
+    let multilingual = text(
+        &[
+            "Größere Straßen führen über die Brücke nach Köln",
+            "İstanbul'da akşam trafiği çok yoğundu, ışıklar yanıyordu",
+            "Ὀδυσσεύς ΚΑΙ Η ΠΗΝΕΛΟΠΗ ΣΤΗΝ ΙΘΑΚΗ",
+            "Москва — столица России, ВЕСНА пришла рано",
+        ],
+        "ΣΥΝΔΕΣΗ Timeout — соединение потеряно, Verbindung unterbrochen",
+        size_mb,
+    );
+
+    // a query starting with an ASCII letter and one that does not, they take different paths
+    for (name, text) in [("ascii", &ascii), ("multilingual", &multilingual)] {
+        for query in ["timeout", "соединение"] {
+            println!(
+                "{} text, {} MB, query {}:",
+                name,
+                text.len() / (1024 * 1024),
+                query
+            );
+            let old = run("to_lowercase", text, |text| {
+                search_insensitive_lowercase(query, text)
+            });
+            let new = run("case fold", text, |text| search_insensitive(query, text));
+            assert_eq!(old, new);
+        }
+    }
+}
@@ -0,0 +1,102 @@
+// compares peak heap usage of the old read_to_string + search_sensitive path
+// with the streaming search_reader path on a large generated file
+// run with: cargo bench --bench memory
+// the size of the file can be changed with MINIGREP_BENCH_MB (default 64)
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use minigrep::{search, search_sensitive, Matcher};
+
+// a global allocator that counts how many bytes are allocated right now and the highest value seen
+struct Counting;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let now = CURRENT.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+            PEAK.fetch_max(now, Ordering::SeqCst);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        CURRENT.fetch_sub(layout.size(), Ordering::SeqCst);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+// runs f and returns its result with the peak heap growth while it was running
+fn measure<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let start = CURRENT.load(Ordering::SeqCst);
+    PEAK.store(start, Ordering::SeqCst);
+    let result = f();
+    (result, PEAK.load(Ordering::SeqCst) - start)
+}
+
+fn mb(bytes: usize) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
+
+fn main() -> io::Result<()> {
+    let size_mb: usize = std::env::var("MINIGREP_BENCH_MB")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(64);
+
+    // generate a log like file, every 1000th line contains the word we search for
+    let path = std::env::temp_dir().join(format!("minigrep_bench_{}.log", std::process::id()));
+    {
+        let mut w = BufWriter::new(File::create(&path)?);
+        let mut written = 0;
+        let mut i = 0u64;
+        while written < size_mb * 1024 * 1024 {
+            let line = if i.is_multiple_of(1000) {
+                format!("{i:>10} ERROR connection timeout while talking to upstream\n")
+            } else {
+                format!("{i:>10} INFO request handled in {} ms\n", i % 97)
+            };
+            written += line.len();
+            w.write_all(line.as_bytes())?;
+            i += 1;
+        }
+    }
+    println!("generated {} MB file at {}", size_mb, path.display());
+
+    let query = "timeout";
+
+    let start = Instant::now();
+    let (count, whole_peak) = measure(|| {
+        let contents = fs::read_to_string(&path).unwrap();
+        search_sensitive(query, &contents).len()
+    });
+    println!(
+        "read_to_string + search_sensitive: {count} matches, peak heap {:.2} MB, {:?}",
+        mb(whole_peak),
+        start.elapsed()
+    );
+
+    let start = Instant::now();
+    let matcher = Matcher::Sensitive(query.to_string());
+    let (count, stream_peak) = measure(|| {
+        let reader = BufReader::new(File::open(&path).unwrap());
+        search::search_reader(&matcher, reader, |_| Ok(())).unwrap()
+    });
+    println!(
+        "search_reader (streaming):         {count} matches, peak heap {:.2} MB, {:?}",
+        mb(stream_peak),
+        start.elapsed()
+    );
+
+    fs::remove_file(&path)?;
+    Ok(())
+}
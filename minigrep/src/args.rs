@@ -0,0 +1,1049 @@
+// command line parsing
+// every option is described once in OPTIONS, the parser and the --help text are both built from that table
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::encoding::Encoding;
+use crate::index::IndexOptions;
+use crate::query::{Query, Scope};
+use crate::{ColorChoice, Config, MinigrepError, OutputMode};
+
+pub struct Opt {
+    pub short: Option<char>,
+    pub long: &'static str,
+    // name of the value shown in --help, None for flags that dont take a value
+    pub value: Option<&'static str>,
+    pub help: &'static str,
+}
+
+pub const OPTIONS: &[Opt] = &[
+    Opt {
+        short: Some('e'),
+        long: "regexp",
+        value: Some("PATTERN"),
+        help: "Search for PATTERN, can be given many times, a line matches if any pattern does",
+    },
+    Opt {
+        short: Some('f'),
+        long: "file",
+        value: Some("FILE"),
+        help: "Read patterns from FILE, one per line",
+    },
+    Opt {
+        short: Some('i'),
+        long: "ignore-case",
+        value: None,
+        help: "Search case insensitively",
+    },
+    Opt {
+        short: Some('s'),
+        long: "case-sensitive",
+        value: None,
+        help: "Search case sensitively (the default unless CASE_INSENSITIVE is set)",
+    },
+    Opt {
+        short: Some('S'),
+        long: "smart-case",
+        value: None,
+        help: "Search case insensitively unless the query has an uppercase letter",
+    },
+    Opt {
+        short: Some('E'),
+        long: "regex",
+        value: None,
+        help: "Treat QUERY as a regular expression",
+    },
+    Opt {
+        short: None,
+        long: "hidden",
+        value: None,
+        help: "Also search hidden files and directories",
+    },
+    Opt {
+        short: None,
+        long: "no-ignore",
+        value: None,
+        help: "Dont skip files listed in .gitignore and .ignore files",
+    },
+    Opt {
+        short: Some('g'),
+        long: "glob",
+        value: Some("GLOB"),
+        help:
+            "Only search files matching GLOB, or skip them with !GLOB, the last matching glob wins",
+    },
+    Opt {
+        short: Some('t'),
+        long: "type",
+        value: Some("TYPE"),
+        help: "Only search files of TYPE, like rust, py or md",
+    },
+    Opt {
+        short: Some('T'),
+        long: "type-not",
+        value: Some("TYPE"),
+        help: "Dont search files of TYPE",
+    },
+    Opt {
+        short: Some('A'),
+        long: "after-context",
+        value: Some("NUM"),
+        help: "Print NUM lines after every match",
+    },
+    Opt {
+        short: Some('B'),
+        long: "before-context",
+        value: Some("NUM"),
+        help: "Print NUM lines before every match",
+    },
+    Opt {
+        short: Some('C'),
+        long: "context",
+        value: Some("NUM"),
+        help: "Print NUM lines before and after every match",
+    },
+    Opt {
+        short: Some('n'),
+        long: "line-number",
+        value: None,
+        help: "Print the line number of every line",
+    },
+    Opt {
+        short: Some('b'),
+        long: "byte-offset",
+        value: None,
+        help: "Print the byte offset of every line, UTF-16 and Latin-1 files count the UTF-8 they decode to",
+    },
+    Opt {
+        short: None,
+        long: "column",
+        value: None,
+        help: "Print the column of the first match in every line",
+    },
+    Opt {
+        short: None,
+        long: "vimgrep",
+        value: None,
+        help: "Print every match as FILE:LINE:COLUMN:TEXT",
+    },
+    Opt {
+        short: None,
+        long: "json",
+        value: None,
+        help: "Print every file, match and context line as a JSON object (offsets as -b counts them)",
+    },
+    Opt {
+        short: Some('v'),
+        long: "invert-match",
+        value: None,
+        help: "Select the lines that do not match",
+    },
+    Opt {
+        short: Some('r'),
+        long: "replace",
+        value: Some("TEXT"),
+        help: "Print lines with every match replaced by TEXT, with -E $1 or ${name} is a group",
+    },
+    Opt {
+        short: None,
+        long: "in-place",
+        value: None,
+        help: "Write the replacements back into the files instead of printing them",
+    },
+    Opt {
+        short: None,
+        long: "backup",
+        value: None,
+        help: "With --in-place keep the old file as FILE.bak",
+    },
+    Opt {
+        short: None,
+        long: "dry-run",
+        value: None,
+        help: "Print a diff of what --in-place would change, without changing anything",
+    },
+    Opt {
+        short: None,
+        long: "encoding",
+        value: Some("ENC"),
+        help: "Read files as utf-8, utf-16, utf-16le, utf-16be or latin-1 (default: from the BOM)",
+    },
+    Opt {
+        short: Some('a'),
+        long: "text",
+        value: None,
+        help: "Print matching lines of binary files too, not just that they match",
+    },
+    Opt {
+        short: Some('w'),
+        long: "word-regexp",
+        value: None,
+        help: "Only match whole words, the text around a match must not be a letter, digit or _",
+    },
+    Opt {
+        short: Some('x'),
+        long: "line-regexp",
+        value: None,
+        help: "Only match whole lines",
+    },
+    Opt {
+        short: None,
+        long: "fuzzy",
+        value: Some("NUM"),
+        help: "Also match substrings at most NUM edits away from the query",
+    },
+    Opt {
+        short: Some('U'),
+        long: "multiline",
+        value: None,
+        help: "Let matches span lines, like \\n in a -E pattern",
+    },
+    Opt {
+        short: None,
+        long: "query",
+        value: None,
+        help: "QUERY is a boolean query: AND, OR, NOT, NEAR/NUM (lines apart) and parentheses",
+    },
+    Opt {
+        short: None,
+        long: "query-scope",
+        value: Some("SCOPE"),
+        help: "Match the query against single lines (line, the default) or whole files (file)",
+    },
+    Opt {
+        short: Some('c'),
+        long: "count",
+        value: None,
+        help: "Only print the number of selected lines of every file",
+    },
+    Opt {
+        short: Some('l'),
+        long: "files-with-matches",
+        value: None,
+        help: "Only print the names of files with a match",
+    },
+    Opt {
+        short: Some('L'),
+        long: "files-without-match",
+        value: None,
+        help: "Only print the names of files without a match",
+    },
+    Opt {
+        short: Some('q'),
+        long: "quiet",
+        value: None,
+        help: "Print nothing and stop at the first match",
+    },
+    Opt {
+        short: None,
+        long: "color",
+        value: Some("WHEN"),
+        help: "Highlight matches: auto (the default), always or never",
+    },
+    Opt {
+        short: Some('j'),
+        long: "threads",
+        value: Some("NUM"),
+        help: "Search NUM files at the same time (default: one per cpu)",
+    },
+    Opt {
+        short: None,
+        long: "index",
+        value: None,
+        help: "Skip the files of a directory its index (see minigrep index) rules out",
+    },
+    Opt {
+        short: None,
+        long: "watch",
+        value: None,
+        help: "Keep running and search again when the files change, appended lines only",
+    },
+    Opt {
+        short: None,
+        long: "no-config",
+        value: None,
+        help: "Dont read default options from the config file",
+    },
+    Opt {
+        short: Some('h'),
+        long: "help",
+        value: None,
+        help: "Print this help and exit",
+    },
+    Opt {
+        short: Some('V'),
+        long: "version",
+        value: None,
+        help: "Print the version and exit",
+    },
+];
+
+// what the user asked us to do
+// Config is big next to Help and Version, but there is only ever one Command so that is fine
+#[allow(clippy::large_enum_variant)]
+pub enum Command {
+    Search(Config),
+    // minigrep index DIR
+    Index(IndexOptions),
+    Help,
+    Version,
+}
+
+pub fn version() -> String {
+    format!("minigrep {}", env!("CARGO_PKG_VERSION"))
+}
+
+pub fn help() -> String {
+    let mut text = format!(
+        "{}\nSearch for QUERY in FILE and print the matching lines.\n\n\
+         Usage: minigrep [search] [OPTIONS] QUERY FILE...\n       \
+         minigrep [search] [OPTIONS] -e PATTERN... FILE...\n       \
+         minigrep index [--hidden] [--no-ignore] [DIR]\n\n\
+         Commands:\n  \
+         search  Search, the default when the first argument is not a command\n  \
+         index   Build or update the trigram index of DIR (default: .) for --index,\n          \
+         only files that changed since the last time are read again\n  \
+         To search for the word search or index, put -- or -e in front of it.\n\n\
+         Arguments:\n  \
+         QUERY  Text to search for (a regular expression with -E)\n  \
+         FILE   Files or directories to search, - reads standard input\n\n\
+         Options:\n",
+        version()
+    );
+
+    for opt in OPTIONS {
+        let short = match opt.short {
+            Some(c) => format!("-{c}, "),
+            None => "    ".to_string(),
+        };
+        let long = match opt.value {
+            Some(value) => format!("--{} <{}>", opt.long, value),
+            None => format!("--{}", opt.long),
+        };
+        text.push_str(&format!("  {short}{long:<24}{}\n", opt.help));
+    }
+
+    text.push_str(
+        "\nEnvironment:\n  \
+         CASE_INSENSITIVE  When set, search case insensitively by default.\n                    \
+         -i, -s and -S on the command line always win over it.\n  \
+         MINIGREP_CONFIG   File with default options, one per line, read before the\n                    \
+         command line (default: ~/.config/minigrep/config). Lines starting\n                    \
+         with # are comments. Put --smart-case in it to make it the default.\n",
+    );
+    text
+}
+
+// parses the arguments the same way as Config::new but reads the environment from the caller,
+// this keeps the rule about the CASE_INSENSITIVE variable testable
+pub fn parse_with_env(
+    args: &[String],
+    case_insensitive_env: bool,
+) -> Result<Command, MinigrepError> {
+    // minigrep search ... is the same as minigrep ..., only index is a command of its own
+    let skip = match args.get(1).map(String::as_str) {
+        Some("index") => return parse_index(&args[2..]),
+        Some("search") => 2,
+        _ => 1,
+    };
+    let mut config = Config {
+        // the environment only decides the default, a flag on the command line always wins
+        case_sensitive: !case_insensitive_env,
+        ..Config::default()
+    };
+    let mut before = None;
+    let mut after = None;
+    let mut both = 0;
+    let mut positional = Vec::new();
+    // patterns from -e and -f, when there are any the QUERY argument is not used
+    let mut patterns: Option<Vec<String>> = None;
+    // everything after -- is positional, even if it starts with a dash
+    let mut only_positional = false;
+
+    let mut args = args.iter().skip(skip);
+    while let Some(arg) = args.next() {
+        if only_positional || arg == "-" || !arg.starts_with('-') {
+            positional.push(arg.clone());
+            continue;
+        }
+        if arg == "--" {
+            only_positional = true;
+            continue;
+        }
+
+        // (option, value) pairs found in this argument
+        let mut found = Vec::new();
+        if let Some(long) = arg.strip_prefix("--") {
+            // --context=3 and --context 3 are both fine
+            let (name, inline) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (long, None),
+            };
+            let opt = OPTIONS
+                .iter()
+                .find(|opt| opt.long == name)
+                .ok_or_else(|| unknown(&format!("--{name}")))?;
+            let value = match (opt.value, inline) {
+                (Some(_), Some(value)) => Some(value),
+                (Some(_), None) => Some(next_value(&mut args, &format!("--{name}"))?),
+                (None, Some(_)) => {
+                    return Err(bad_args(format!("option '--{name}' does not take a value")))
+                }
+                (None, None) => None,
+            };
+            found.push((opt, value));
+        } else {
+            // a group of short flags like -in, the last one may take a value like -nC3 or -nC 3
+            let flags = &arg[1..];
+            for (i, c) in flags.char_indices() {
+                let opt = OPTIONS
+                    .iter()
+                    .find(|opt| opt.short == Some(c))
+                    .ok_or_else(|| unknown(&format!("-{c}")))?;
+                if opt.value.is_some() {
+                    let rest = &flags[i + c.len_utf8()..];
+                    let value = if rest.is_empty() {
+                        next_value(&mut args, &format!("-{c}"))?
+                    } else {
+                        rest.to_string()
+                    };
+                    found.push((opt, Some(value)));
+                    break;
+                }
+                found.push((opt, None));
+            }
+        }
+
+        for (opt, value) in found {
+            let value = value.unwrap_or_default();
+            match opt.long {
+                "help" => return Ok(Command::Help),
+                "version" => return Ok(Command::Version),
+                "regexp" => patterns.get_or_insert_with(Vec::new).push(value),
+                "file" => {
+                    let contents =
+                        fs::read_to_string(&value).map_err(|e| MinigrepError::text(&value, e))?;
+                    patterns
+                        .get_or_insert_with(Vec::new)
+                        .extend(contents.lines().map(|line| line.to_string()));
+                }
+                // the last of -i, -s and -S wins
+                "ignore-case" | "case-sensitive" => {
+                    config.case_sensitive = opt.long == "case-sensitive";
+                    config.smart_case = false;
+                }
+                "smart-case" => config.smart_case = true,
+                "no-config" => {} // already handled by with_config_file
+                "index" => config.index = true,
+                "watch" => config.watch = true,
+                "regex" => config.regex = true,
+                "hidden" => config.hidden = true,
+                "no-ignore" => config.no_ignore = true,
+                "glob" => config.globs.push(value),
+                "type" => config.types.push(value),
+                "type-not" => config.types_not.push(value),
+                "after-context" => after = Some(parse_number(opt, &value)?),
+                "before-context" => before = Some(parse_number(opt, &value)?),
+                "context" => both = parse_number(opt, &value)?,
+                "line-number" => config.line_number = true,
+                "byte-offset" => config.byte_offset = true,
+                "column" => config.column = true,
+                "vimgrep" => config.vimgrep = true,
+                "json" => config.json = true,
+                "encoding" => {
+                    config.encoding = Some(Encoding::from_name(&value).ok_or_else(|| {
+                        bad_args(format!(
+                            "option '--encoding' must be utf-8, utf-16, utf-16le, utf-16be or latin-1, got '{value}'"
+                        ))
+                    })?)
+                }
+                "replace" => config.replace = Some(value),
+                "in-place" => config.in_place = true,
+                "backup" => config.backup = true,
+                "dry-run" => config.dry_run = true,
+                "text" => config.text = true,
+                "word-regexp" => config.word_regexp = true,
+                "line-regexp" => config.line_regexp = true,
+                "fuzzy" => config.fuzzy = Some(parse_number(opt, &value)?),
+                "multiline" => config.multiline = true,
+                "query" => {
+                    config.boolean_query.get_or_insert(Scope::Line);
+                }
+                // a scope is only useful for a query, so it turns --query on too
+                "query-scope" => {
+                    config.boolean_query = Some(match value.as_str() {
+                        "line" => Scope::Line,
+                        "file" => Scope::File,
+                        _ => {
+                            return Err(bad_args(format!(
+                                "option '--query-scope' must be line or file, got '{value}'"
+                            )))
+                        }
+                    })
+                }
+                "invert-match" => config.invert = true,
+                // only one output mode at a time, the last one wins
+                "count" => config.output = OutputMode::Count,
+                "files-with-matches" => config.output = OutputMode::FilesWithMatches,
+                "files-without-match" => config.output = OutputMode::FilesWithoutMatch,
+                "quiet" => config.output = OutputMode::Quiet,
+                "threads" => config.threads = parse_number(opt, &value)?,
+                "color" => {
+                    config.color = match value.as_str() {
+                        "auto" => ColorChoice::Auto,
+                        "always" => ColorChoice::Always,
+                        "never" => ColorChoice::Never,
+                        _ => {
+                            return Err(bad_args(format!(
+                                "option '--color' must be auto, always or never, got '{value}'"
+                            )))
+                        }
+                    }
+                }
+                _ => unreachable!("option --{} is in OPTIONS but not handled", opt.long),
+            }
+        }
+    }
+
+    // Error Handling
+    let not_enough =
+        || bad_args("Not enough arguments, usage: minigrep [OPTIONS] QUERY FILE".to_string());
+    let mut positional = positional.into_iter();
+    // with -e or -f every argument is a file, otherwise the first one is the query
+    config.patterns = match patterns {
+        Some(patterns) => patterns,
+        None => vec![positional.next().ok_or_else(not_enough)?],
+    };
+    config.filenames = positional.collect();
+    if config.filenames.is_empty() {
+        return Err(not_enough());
+    }
+
+    // -A and -B win over -C, the same as in grep
+    config.before_context = before.unwrap_or(both);
+    config.after_context = after.unwrap_or(both);
+
+    if (config.in_place || config.dry_run || config.backup) && config.replace.is_none() {
+        return Err(bad_args(
+            "--in-place, --backup and --dry-run need --replace".to_string(),
+        ));
+    }
+
+    // the edit distance is between the query and plain text, so only one plain query
+    if config.fuzzy.is_some()
+        && (config.patterns.len() != 1 || config.regex || config.word_regexp || config.line_regexp)
+    {
+        return Err(bad_args(
+            "--fuzzy needs exactly one query and can not be used with -E, -w or -x".to_string(),
+        ));
+    }
+
+    // a rewrite goes line by line, it has no way to join lines
+    if config.multiline && (config.in_place || config.dry_run) {
+        return Err(bad_args(
+            "--multiline can not be used with --in-place or --dry-run".to_string(),
+        ));
+    }
+
+    if let Some(scope) = config.boolean_query {
+        if config.patterns.len() != 1
+            || config.fuzzy.is_some()
+            || config.multiline
+            || config.replace.is_some()
+        {
+            return Err(bad_args(
+                "--query needs exactly one query and can not be used with --fuzzy, --multiline or --replace"
+                    .to_string(),
+            ));
+        }
+        // a whole file matches or not, there are no lines to print or count, only its name
+        if scope == Scope::File {
+            if config.output == OutputMode::Count || config.json {
+                return Err(bad_args(
+                    "--query-scope file can not be used with -c or --json".to_string(),
+                ));
+            }
+            if config.output == OutputMode::Lines {
+                config.output = OutputMode::FilesWithMatches;
+            }
+        }
+    }
+
+    // --json is an output mode of its own, it shows everything -c, -l and friends would
+    if config.json
+        && (config.output != OutputMode::Lines
+            || config.vimgrep
+            || config.in_place
+            || config.dry_run)
+    {
+        return Err(bad_args(
+            "--json can not be used with -c, -l, -L, -q, --vimgrep, --in-place or --dry-run"
+                .to_string(),
+        ));
+    }
+
+    // --watch prints lines as they show up, there is no end to print a count or a file name at
+    // and standard input can not be read twice
+    if config.watch
+        && (config.output != OutputMode::Lines
+            || config.json
+            || config.in_place
+            || config.dry_run
+            || config.filenames.iter().any(|filename| filename == "-"))
+    {
+        return Err(bad_args(
+            "--watch can not be used with -c, -l, -L, -q, --query-scope file, --json, --in-place, --dry-run or standard input"
+                .to_string(),
+        ));
+    }
+
+    // with --smart-case an uppercase letter anywhere in the patterns makes the search case sensitive
+    // in a --query only the terms count, AND and OR are not what we search for
+    if config.smart_case {
+        let query = match config.boolean_query {
+            Some(_) => Some(Query::parse(&config.patterns[0])?),
+            None => None,
+        };
+        let patterns = query.as_ref().map_or(&config.patterns[..], Query::terms);
+        config.case_sensitive = patterns
+            .iter()
+            .any(|pattern| has_uppercase(pattern, config.regex));
+    }
+
+    Ok(Command::Search(config))
+}
+
+// minigrep index [--hidden] [--no-ignore] [DIR], the options of a search make no sense here
+fn parse_index(args: &[String]) -> Result<Command, MinigrepError> {
+    let mut options = IndexOptions::default();
+    let mut dirs = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--hidden" => options.hidden = true,
+            "--no-ignore" => options.no_ignore = true,
+            "-h" | "--help" => return Ok(Command::Help),
+            flag if flag.starts_with('-') => {
+                return Err(bad_args(format!(
+                    "unknown option '{flag}' for minigrep index, it knows --hidden and --no-ignore"
+                )))
+            }
+            dir => dirs.push(dir),
+        }
+    }
+    options.dir = match dirs.as_slice() {
+        [] => PathBuf::from("."),
+        [dir] => PathBuf::from(dir),
+        _ => return Err(bad_args("minigrep index takes one DIR".to_string())),
+    };
+    Ok(Command::Index(options))
+}
+
+// in a regex \S or \p{Greek} dont count, they are not letters the user wants to match
+pub(crate) fn has_uppercase(pattern: &str, regex: bool) -> bool {
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if regex && c == '\\' {
+            // skip the escaped char, and the name of a Unicode class like \p{Greek} too
+            if let Some('p' | 'P') = chars.next() {
+                if chars.clone().next() == Some('{') {
+                    chars.by_ref().find(|&c| c == '}');
+                } else {
+                    chars.next();
+                }
+            }
+        } else if c.is_uppercase() {
+            return true;
+        }
+    }
+    false
+}
+
+// where the config file is, see config_file
+pub struct ConfigFile {
+    pub path: PathBuf,
+    // only a file named in MINIGREP_CONFIG has to exist
+    pub must_exist: bool,
+}
+
+// the config file is MINIGREP_CONFIG, or ~/.config/minigrep/config when that is not set
+// an empty MINIGREP_CONFIG turns it off, main looks it up and hands it to with_config_file
+// so nothing in the library depends on the home directory of whoever runs it
+pub fn config_file() -> Option<ConfigFile> {
+    let (path, must_exist) = if let Some(path) = env::var_os("MINIGREP_CONFIG") {
+        (PathBuf::from(path), true)
+    } else if let Some(dir) = env::var_os("XDG_CONFIG_HOME") {
+        (PathBuf::from(dir).join("minigrep/config"), false)
+    } else if let Some(home) = env::var_os("HOME") {
+        (PathBuf::from(home).join(".config/minigrep/config"), false)
+    } else {
+        return None;
+    };
+    (!path.as_os_str().is_empty()).then_some(ConfigFile { path, must_exist })
+}
+
+// puts the options from the config file between the program name and the real arguments,
+// so anything on the command line still wins over them, --no-config leaves them out
+pub fn with_config_file(
+    args: &[String],
+    file: Option<&ConfigFile>,
+) -> Result<Vec<String>, MinigrepError> {
+    let no_config = args
+        .iter()
+        .skip(1)
+        .take_while(|arg| *arg != "--")
+        .any(|arg| arg == "--no-config");
+    let Some(ConfigFile { path, must_exist }) = file else {
+        return Ok(args.to_vec());
+    };
+    // the options go after the command, minigrep index has none we could use
+    let command = match args.get(1).map(String::as_str) {
+        Some("index") => return Ok(args.to_vec()),
+        Some("search") => 2,
+        _ => 1,
+    };
+    if no_config {
+        return Ok(args.to_vec());
+    }
+
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound && !*must_exist => return Ok(args.to_vec()),
+        Err(e) => return Err(MinigrepError::text(&path.display().to_string(), e)),
+    };
+    let mut all = args[..args.len().min(command)].to_vec();
+    all.extend(config_file_args(&contents));
+    all.extend(args.iter().skip(command).cloned());
+    Ok(all)
+}
+
+// one argument per line, so --glob=*.rs or -g and *.rs on two lines, no shell quoting
+fn config_file_args(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.to_string())
+        .collect()
+}
+
+fn bad_args(message: String) -> MinigrepError {
+    MinigrepError::BadArgs(message)
+}
+
+fn unknown(flag: &str) -> MinigrepError {
+    bad_args(format!(
+        "unknown option '{flag}', try 'minigrep --help' for the list of options"
+    ))
+}
+
+fn next_value<'a>(
+    args: &mut impl Iterator<Item = &'a String>,
+    flag: &str,
+) -> Result<String, MinigrepError> {
+    args.next()
+        .cloned()
+        .ok_or_else(|| bad_args(format!("option '{flag}' needs a value")))
+}
+
+fn parse_number(opt: &Opt, value: &str) -> Result<usize, MinigrepError> {
+    value.parse().map_err(|_| {
+        bad_args(format!(
+            "option '--{}' needs a number, got '{}'",
+            opt.long, value
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str], env: bool) -> Result<Command, MinigrepError> {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        parse_with_env(&args, env)
+    }
+
+    fn config(args: &[&str], env: bool) -> Config {
+        match parse(args, env) {
+            Ok(Command::Search(config)) => config,
+            _ => panic!("expected a search for {:?}", args),
+        }
+    }
+
+    #[test]
+    fn short_long_and_grouped_flags() {
+        let config = config(
+            &["minigrep", "-nE", "--context=2", "-A1", "to", "poem.txt"],
+            false,
+        );
+        assert!(config.line_number && config.regex);
+        assert_eq!((2, 1), (config.before_context, config.after_context));
+
+        let config = self::config(
+            &["minigrep", "--after-context", "3", "to", "-b", "poem.txt"],
+            false,
+        );
+        assert!(config.byte_offset);
+        assert_eq!((0, 3), (config.before_context, config.after_context));
+        assert_eq!(
+            ("to", "poem.txt"),
+            (config.patterns[0].as_str(), config.filenames[0].as_str())
+        );
+    }
+
+    #[test]
+    fn many_patterns() {
+        let config = config(
+            &["minigrep", "-e", "foo", "--regexp=bar", "-ebaz", "poem.txt"],
+            false,
+        );
+        assert_eq!(vec!["foo", "bar", "baz"], config.patterns);
+        assert_eq!(vec!["poem.txt"], config.filenames);
+
+        let file = std::env::temp_dir().join(format!("minigrep_patterns_{}", std::process::id()));
+        fs::write(&file, "one\ntwo\n").unwrap();
+        let config = self::config(
+            &[
+                "minigrep",
+                "-f",
+                file.to_str().unwrap(),
+                "-e",
+                "three",
+                "poem.txt",
+            ],
+            false,
+        );
+        assert_eq!(vec!["one", "two", "three"], config.patterns);
+        fs::remove_file(&file).unwrap();
+
+        // with -e there is no QUERY argument, but FILE is still needed
+        assert!(parse(&["minigrep", "-e", "foo"], false).is_err());
+        assert!(parse(&["minigrep", "-f", "/no/such/file", "poem.txt"], false).is_err());
+    }
+
+    #[test]
+    fn file_selection() {
+        let config = config(
+            &[
+                "minigrep",
+                "-g",
+                "*.rs",
+                "--glob=!target/**",
+                "-trust",
+                "-T",
+                "md",
+                "--no-ignore",
+                "a",
+                ".",
+            ],
+            false,
+        );
+        assert_eq!(vec!["*.rs", "!target/**"], config.globs);
+        assert_eq!(vec!["rust"], config.types);
+        assert_eq!(vec!["md"], config.types_not);
+        assert!(config.no_ignore);
+    }
+
+    #[test]
+    fn output_modes() {
+        assert_eq!(
+            OutputMode::Lines,
+            config(&["minigrep", "a", "b"], false).output
+        );
+        let config = config(&["minigrep", "-vc", "-l", "a", "b"], false);
+        assert!(config.invert);
+        assert_eq!(OutputMode::FilesWithMatches, config.output);
+        assert_eq!(ColorChoice::Auto, config.color);
+
+        let config = self::config(&["minigrep", "--encoding=UTF-16", "a", "b"], false);
+        assert_eq!(Some(Encoding::Utf16), config.encoding);
+        assert!(parse(&["minigrep", "--encoding", "ebcdic", "a", "b"], false).is_err());
+
+        let config = self::config(
+            &["minigrep", "-r", "$1", "--in-place", "--backup", "a", "b"],
+            false,
+        );
+        assert_eq!(Some("$1".to_string()), config.replace);
+        assert!(config.in_place && config.backup && !config.dry_run);
+        assert!(parse(&["minigrep", "--dry-run", "a", "b"], false).is_err());
+
+        let config = self::config(&["minigrep", "-wx", "a", "b"], false);
+        assert!(config.word_regexp && config.line_regexp);
+
+        assert_eq!(
+            Some(2),
+            self::config(&["minigrep", "--fuzzy=2", "a", "b"], false).fuzzy
+        );
+        assert!(parse(&["minigrep", "--fuzzy", "1", "-E", "a", "b"], false).is_err());
+
+        assert!(self::config(&["minigrep", "-U", "a", "b"], false).multiline);
+        assert!(parse(
+            &["minigrep", "-U", "-r", "x", "--in-place", "a", "b"],
+            false
+        )
+        .is_err());
+
+        let config = self::config(&["minigrep", "--query", "a OR b", "c"], false);
+        assert_eq!(Some(Scope::Line), config.boolean_query);
+        assert_eq!(OutputMode::Lines, config.output);
+        let config = self::config(&["minigrep", "--query-scope=file", "a", "c"], false);
+        assert_eq!(Some(Scope::File), config.boolean_query);
+        assert_eq!(OutputMode::FilesWithMatches, config.output);
+        assert!(parse(&["minigrep", "--query", "-e", "a", "-e", "b", "c"], false).is_err());
+        assert!(parse(&["minigrep", "--query-scope=file", "-c", "a", "c"], false).is_err());
+        assert!(parse(&["minigrep", "--query-scope", "word", "a", "c"], false).is_err());
+
+        assert!(self::config(&["minigrep", "--json", "a", "b"], false).json);
+        assert!(parse(&["minigrep", "--json", "-c", "a", "b"], false).is_err());
+
+        assert!(self::config(&["minigrep", "--watch", "a", "b"], false).watch);
+        assert!(parse(&["minigrep", "--watch", "-l", "a", "b"], false).is_err());
+        assert!(parse(&["minigrep", "--watch", "a"], false).is_err());
+
+        let config = self::config(&["minigrep", "--color=never", "a", "b"], false);
+        assert_eq!(ColorChoice::Never, config.color);
+        assert!(parse(&["minigrep", "--color", "pink", "a", "b"], false).is_err());
+    }
+
+    #[test]
+    fn double_dash_ends_options() {
+        let config = config(&["minigrep", "-n", "--", "-v", "-"], false);
+        assert_eq!(vec!["-v"], config.patterns);
+        assert_eq!(vec!["-"], config.filenames);
+        assert!(config.line_number);
+    }
+
+    #[test]
+    fn errors() {
+        let err = |args: &[&str]| parse(args, false).err().unwrap().to_string();
+        assert!(err(&["minigrep", "--nope", "a", "b"]).contains("unknown option '--nope'"));
+        assert!(err(&["minigrep", "-nZ", "a", "b"]).contains("unknown option '-Z'"));
+        assert!(err(&["minigrep", "a", "b", "-C"]).contains("needs a value"));
+        assert!(err(&["minigrep", "-C", "lots", "a", "b"]).contains("needs a number"));
+        assert!(err(&["minigrep", "--column=1", "a", "b"]).contains("does not take a value"));
+        assert!(err(&["minigrep", "a"]).contains("Not enough arguments"));
+    }
+
+    #[test]
+    fn help_and_version() {
+        assert!(matches!(
+            parse(&["minigrep", "--help"], false),
+            Ok(Command::Help)
+        ));
+        assert!(matches!(
+            parse(&["minigrep", "a", "-V"], false),
+            Ok(Command::Version)
+        ));
+
+        let help = help();
+        for opt in OPTIONS {
+            assert!(help.contains(&format!("--{}", opt.long)));
+        }
+    }
+
+    #[test]
+    fn flags_win_over_the_environment() {
+        // no flag: the environment decides
+        assert!(config(&["minigrep", "a", "b"], false).case_sensitive);
+        assert!(!config(&["minigrep", "a", "b"], true).case_sensitive);
+
+        // a flag always wins, whatever the environment says
+        assert!(config(&["minigrep", "-s", "a", "b"], true).case_sensitive);
+        assert!(!config(&["minigrep", "-i", "a", "b"], false).case_sensitive);
+
+        // between two flags the last one wins
+        assert!(config(&["minigrep", "-i", "-s", "a", "b"], false).case_sensitive);
+        assert!(!config(&["minigrep", "-s", "-i", "a", "b"], true).case_sensitive);
+    }
+
+    #[test]
+    fn smart_case() {
+        assert!(!config(&["minigrep", "-S", "frog", "b"], false).case_sensitive);
+        assert!(config(&["minigrep", "-S", "Frog", "b"], true).case_sensitive);
+        assert!(
+            config(
+                &["minigrep", "--smart-case", "-e", "a", "-e", "Öl", "b"],
+                false
+            )
+            .case_sensitive
+        );
+        // escapes in a regex are not uppercase letters
+        assert!(!config(&["minigrep", "-SE", r"\Sfoo\p{Greek}\W", "b"], false).case_sensitive);
+        assert!(config(&["minigrep", "-S", r"\Sfoo", "b"], false).case_sensitive);
+        // a later -i or -s turns it off again, and -S after them turns it back on
+        assert!(!config(&["minigrep", "-S", "-i", "Frog", "b"], false).case_sensitive);
+        assert!(config(&["minigrep", "-i", "-S", "Frog", "b"], false).case_sensitive);
+        // the operators of a query are not uppercase letters either
+        assert!(!config(&["minigrep", "-S", "--query", "a AND NOT b", "c"], false).case_sensitive);
+        assert!(config(&["minigrep", "-S", "--query", "a OR Bee", "c"], false).case_sensitive);
+    }
+
+    #[test]
+    fn commands() {
+        let config = config(&["minigrep", "search", "--index", "-n", "a", "b"], false);
+        assert!(config.index && config.line_number);
+        assert_eq!(vec!["a"], config.patterns);
+        // only the first argument can be a command
+        assert_eq!(
+            vec!["search"],
+            self::config(&["minigrep", "--", "search", "b"], false).patterns
+        );
+        assert_eq!(
+            vec!["index"],
+            self::config(&["minigrep", "-e", "index", "b"], false).patterns
+        );
+
+        match parse(&["minigrep", "index", "--hidden", "docs"], false) {
+            Ok(Command::Index(options)) => {
+                assert_eq!(PathBuf::from("docs"), options.dir);
+                assert!(options.hidden && !options.no_ignore);
+            }
+            _ => panic!("expected minigrep index"),
+        }
+        assert!(matches!(
+            parse(&["minigrep", "index"], false),
+            Ok(Command::Index(IndexOptions { ref dir, .. })) if dir == &PathBuf::from(".")
+        ));
+        assert!(parse(&["minigrep", "index", "-n", "docs"], false).is_err());
+        assert!(parse(&["minigrep", "index", "a", "b"], false).is_err());
+    }
+
+    #[test]
+    fn config_file_args_come_first() {
+        let args = config_file_args("# my defaults\n--smart-case\n\n  -g\n*.rs\n");
+        assert_eq!(vec!["--smart-case", "-g", "*.rs"], args);
+
+        let path = std::env::temp_dir().join(format!("minigrep_config_{}", std::process::id()));
+        std::fs::write(&path, "-n\n").unwrap();
+        let required = ConfigFile {
+            path: path.clone(),
+            must_exist: true,
+        };
+        let args = |args: &[&str], file| {
+            let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+            with_config_file(&args, file)
+        };
+        assert_eq!(
+            vec!["minigrep", "search", "-n", "a", "b"],
+            args(&["minigrep", "search", "a", "b"], Some(&required)).unwrap()
+        );
+        assert_eq!(
+            vec!["minigrep", "--no-config", "a", "b"],
+            args(&["minigrep", "--no-config", "a", "b"], Some(&required)).unwrap()
+        );
+        assert_eq!(
+            vec!["minigrep", "a"],
+            args(&["minigrep", "a"], None).unwrap()
+        );
+        std::fs::remove_file(&path).unwrap();
+        // a file named in MINIGREP_CONFIG has to be there, the default one does not
+        assert!(args(&["minigrep", "a", "b"], Some(&required)).is_err());
+        let file = ConfigFile {
+            path,
+            must_exist: false,
+        };
+        assert!(args(&["minigrep", "a", "b"], Some(&file)).is_ok());
+    }
+}
@@ -0,0 +1,424 @@
+// files that are not UTF-8, like the UTF-16 logs Windows tools write
+// we turn them into UTF-8 while reading, so the search itself only ever sees UTF-8
+use std::io::{self, BufRead, Read};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Encoding {
+    Utf8,
+    // UTF-16 in the byte order of its BOM, little endian when there is none
+    Utf16,
+    Utf16Le,
+    Utf16Be,
+    // every byte is one char, ISO-8859-1
+    Latin1,
+}
+
+impl Encoding {
+    // the names --encoding understands
+    pub fn from_name(name: &str) -> Option<Encoding> {
+        match name.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Some(Encoding::Utf8),
+            "utf-16" | "utf16" => Some(Encoding::Utf16),
+            "utf-16le" | "utf16le" => Some(Encoding::Utf16Le),
+            "utf-16be" | "utf16be" => Some(Encoding::Utf16Be),
+            "latin-1" | "latin1" | "iso-8859-1" => Some(Encoding::Latin1),
+            _ => None,
+        }
+    }
+}
+
+// the byte order mark at the start of a file tells us its encoding, and how long it is
+fn sniff(start: &[u8]) -> Option<(Encoding, usize)> {
+    if start.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        Some((Encoding::Utf8, 3))
+    } else if start.starts_with(&[0xFF, 0xFE]) {
+        Some((Encoding::Utf16Le, 2))
+    } else if start.starts_with(&[0xFE, 0xFF]) {
+        Some((Encoding::Utf16Be, 2))
+    } else {
+        None
+    }
+}
+
+// a reader that gives out UTF-8, either straight from the inner reader or transcoded
+pub enum Decoded<R> {
+    Utf8(R),
+    Transcoded(Transcoder<R>),
+}
+
+// the encoding a file starting with start is read in, and how long its BOM is (0 when the BOM
+// is of another encoding, then it is just text), forced is the --encoding flag
+pub fn detect(start: &[u8], forced: Option<Encoding>) -> (Encoding, usize) {
+    let sniffed = sniff(start);
+    let encoding = match (forced, sniffed) {
+        (None, Some((sniffed, _))) => sniffed,
+        (None, None) => Encoding::Utf8,
+        (Some(Encoding::Utf16), Some((sniffed @ (Encoding::Utf16Le | Encoding::Utf16Be), _))) => {
+            sniffed
+        }
+        (Some(Encoding::Utf16), _) => Encoding::Utf16Le,
+        (Some(forced), _) => forced,
+    };
+    match sniffed {
+        Some((sniffed, len)) if sniffed == encoding => (encoding, len),
+        _ => (encoding, 0),
+    }
+}
+
+// looks at the BOM and wraps reader so it gives out UTF-8, forced is the --encoding flag
+// the BOM itself is never part of the output
+pub fn decode<R: BufRead>(mut reader: R, forced: Option<Encoding>) -> io::Result<Decoded<R>> {
+    let (encoding, bom) = detect(reader.fill_buf()?, forced);
+    reader.consume(bom);
+    Ok(transcode(reader, encoding))
+}
+
+// the same when the encoding is known already and reader starts after the BOM, like the
+// lines --watch reads from the middle of a file
+pub fn transcode<R: BufRead>(reader: R, encoding: Encoding) -> Decoded<R> {
+    match encoding {
+        Encoding::Utf8 => Decoded::Utf8(reader),
+        _ => Decoded::Transcoded(Transcoder {
+            inner: reader,
+            encoding,
+            raw: Vec::new(),
+            out: Vec::new(),
+            pos: 0,
+        }),
+    }
+}
+
+// counts how many bytes of UTF-8 the Transcoder makes out of some text, a few bytes at a time
+// (--watch needs it for -b after the lines it searched already)
+// the count is only right at the end of a line, a char can still be half read anywhere else
+#[derive(Debug)]
+pub struct Utf8Len {
+    encoding: Encoding,
+    // the first byte of a UTF-16 unit, and whether the last unit was the first half of a pair
+    half: Option<u8>,
+    high: bool,
+    pub len: u64,
+}
+
+impl Utf8Len {
+    pub fn new(encoding: Encoding, len: u64) -> Utf8Len {
+        Utf8Len {
+            encoding,
+            half: None,
+            high: false,
+            len,
+        }
+    }
+
+    pub fn count(&mut self, bytes: &[u8]) {
+        let big_endian = match self.encoding {
+            Encoding::Utf8 => {
+                self.len += bytes.len() as u64;
+                return;
+            }
+            // everything from 0x80 on takes two bytes
+            Encoding::Latin1 => {
+                let high = bytes.iter().filter(|&&b| b >= 0x80).count();
+                self.len += (bytes.len() + high) as u64;
+                return;
+            }
+            Encoding::Utf16Be => true,
+            Encoding::Utf16 | Encoding::Utf16Le => false,
+        };
+        for &b in bytes {
+            let Some(first) = self.half.take() else {
+                self.half = Some(b);
+                continue;
+            };
+            let unit = if big_endian {
+                u16::from_be_bytes([first, b])
+            } else {
+                u16::from_le_bytes([first, b])
+            };
+            // a half of a pair on its own turns into a U+FFFD, 3 bytes like every other surrogate
+            let high = std::mem::replace(&mut self.high, false);
+            self.len += match unit {
+                0xD800..=0xDBFF => {
+                    self.high = true;
+                    if high {
+                        3
+                    } else {
+                        0
+                    }
+                }
+                0xDC00..=0xDFFF if high => 4,
+                0xDC00..=0xDFFF => 3,
+                _ => {
+                    let len = char::from_u32(unit as u32).map_or(3, char::len_utf8) as u64;
+                    if high {
+                        len + 3
+                    } else {
+                        len
+                    }
+                }
+            };
+        }
+    }
+}
+
+// a whole file (without its BOM) as text, for --in-place that writes it back in the same encoding
+// None when it is not valid in that encoding, a � would end up in the file instead of those bytes
+pub fn decode_exact(bytes: &[u8], encoding: Encoding) -> Option<String> {
+    match encoding {
+        Encoding::Utf8 => String::from_utf8(bytes.to_vec()).ok(),
+        Encoding::Latin1 => Some(bytes.iter().map(|&b| b as char).collect()),
+        _ => {
+            if !bytes.len().is_multiple_of(2) {
+                return None;
+            }
+            let big_endian = encoding == Encoding::Utf16Be;
+            let units = bytes.chunks_exact(2).map(|pair| {
+                let pair = [pair[0], pair[1]];
+                if big_endian {
+                    u16::from_be_bytes(pair)
+                } else {
+                    u16::from_le_bytes(pair)
+                }
+            });
+            char::decode_utf16(units).collect::<Result<_, _>>().ok()
+        }
+    }
+}
+
+// the other way around, None when a char does not exist in Latin-1
+pub fn encode(text: &str, encoding: Encoding) -> Option<Vec<u8>> {
+    match encoding {
+        Encoding::Utf8 => Some(text.as_bytes().to_vec()),
+        Encoding::Latin1 => text.chars().map(|c| u8::try_from(c).ok()).collect(),
+        Encoding::Utf16Be => Some(text.encode_utf16().flat_map(u16::to_be_bytes).collect()),
+        Encoding::Utf16 | Encoding::Utf16Le => {
+            Some(text.encode_utf16().flat_map(u16::to_le_bytes).collect())
+        }
+    }
+}
+
+// turns UTF-16 or Latin-1 into UTF-8 while it is read, the search after it only ever sees the UTF-8,
+// so the offsets of -b and --json count UTF-8 bytes and not the bytes in the file
+pub struct Transcoder<R> {
+    inner: R,
+    encoding: Encoding,
+    // bytes read from inner that did not make a whole char yet
+    raw: Vec<u8>,
+    // UTF-8 that was not handed out yet starts at out[pos]
+    out: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: BufRead> Transcoder<R> {
+    // turns as much of raw into UTF-8 as possible, at_end means nothing more will come
+    fn transcode(&mut self, at_end: bool) {
+        let used = match self.encoding {
+            Encoding::Latin1 => {
+                for &b in &self.raw {
+                    let mut utf8 = [0; 2];
+                    self.out
+                        .extend_from_slice((b as char).encode_utf8(&mut utf8).as_bytes());
+                }
+                self.raw.len()
+            }
+            _ => {
+                let big_endian = self.encoding == Encoding::Utf16Be;
+                let mut units: Vec<u16> = self
+                    .raw
+                    .chunks_exact(2)
+                    .map(|pair| {
+                        let pair = [pair[0], pair[1]];
+                        if big_endian {
+                            u16::from_be_bytes(pair)
+                        } else {
+                            u16::from_le_bytes(pair)
+                        }
+                    })
+                    .collect();
+                // the second half of a surrogate pair may still be in the next block
+                if !at_end && units.last().is_some_and(|u| (0xD800..0xDC00).contains(u)) {
+                    units.pop();
+                }
+                let decoded: String = char::decode_utf16(units.iter().copied())
+                    .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                    .collect();
+                self.out.extend_from_slice(decoded.as_bytes());
+                units.len() * 2
+            }
+        };
+        self.raw.drain(..used);
+        // half a char at the very end of the file
+        if at_end && !self.raw.is_empty() {
+            self.raw.clear();
+            self.out.extend_from_slice("\u{FFFD}".as_bytes());
+        }
+    }
+}
+
+impl<R: BufRead> BufRead for Transcoder<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        while self.pos == self.out.len() {
+            self.out.clear();
+            self.pos = 0;
+            let block = self.inner.fill_buf()?;
+            if block.is_empty() {
+                if self.raw.is_empty() {
+                    break; // end of input
+                }
+                self.transcode(true);
+                continue;
+            }
+            let len = block.len();
+            self.raw.extend_from_slice(block);
+            self.inner.consume(len);
+            self.transcode(false);
+        }
+        Ok(&self.out[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.out.len());
+    }
+}
+
+impl<R: BufRead> Read for Transcoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for Decoded<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        match self {
+            Decoded::Utf8(reader) => reader.fill_buf(),
+            Decoded::Transcoded(reader) => reader.fill_buf(),
+        }
+    }
+
+    fn consume(&mut self, amt: usize) {
+        match self {
+            Decoded::Utf8(reader) => reader.consume(amt),
+            Decoded::Transcoded(reader) => reader.consume(amt),
+        }
+    }
+}
+
+impl<R: BufRead> Read for Decoded<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Decoded::Utf8(reader) => reader.read(buf),
+            Decoded::Transcoded(reader) => reader.read(buf),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufReader;
+
+    fn decode_all(bytes: &[u8], forced: Option<Encoding>) -> String {
+        // a tiny buffer, so chars are split between blocks
+        let mut decoded = decode(BufReader::with_capacity(3, bytes), forced).unwrap();
+        let mut text = String::new();
+        decoded.read_to_string(&mut text).unwrap();
+        text
+    }
+
+    fn utf16(text: &str, big_endian: bool) -> Vec<u8> {
+        text.encode_utf16()
+            .flat_map(|u| {
+                if big_endian {
+                    u.to_be_bytes()
+                } else {
+                    u.to_le_bytes()
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn boms_are_sniffed() {
+        let text = "Größe 🦀\r\nzwei\n";
+        let mut le = vec![0xFF, 0xFE];
+        le.extend(utf16(text, false));
+        assert_eq!(text, decode_all(&le, None));
+        let mut be = vec![0xFE, 0xFF];
+        be.extend(utf16(text, true));
+        assert_eq!(text, decode_all(&be, None));
+        assert_eq!(text, decode_all(&be, Some(Encoding::Utf16)));
+
+        let mut utf8 = vec![0xEF, 0xBB, 0xBF];
+        utf8.extend(text.bytes());
+        assert_eq!(text, decode_all(&utf8, None));
+        // without a BOM it is UTF-8
+        assert_eq!(text, decode_all(text.as_bytes(), None));
+    }
+
+    #[test]
+    fn forced_encodings() {
+        assert_eq!("café", decode_all(b"caf\xe9", Some(Encoding::Latin1)));
+        assert_eq!("hi", decode_all(&utf16("hi", false), Some(Encoding::Utf16)));
+        assert_eq!(
+            "hi",
+            decode_all(&utf16("hi", true), Some(Encoding::Utf16Be))
+        );
+        // a lone surrogate and half a unit at the end become �
+        let mut broken = utf16("a", false);
+        broken.extend([0x00, 0xD8, 0x62]);
+        assert_eq!(
+            "a\u{FFFD}\u{FFFD}",
+            decode_all(&broken, Some(Encoding::Utf16Le))
+        );
+        assert_eq!(None, Encoding::from_name("ebcdic"));
+    }
+
+    #[test]
+    fn utf8_len_counts_what_the_transcoder_makes() {
+        let text = "Größe 🦀\nzwei\n";
+        let mut lone = utf16("a", false);
+        lone.extend([0x00, 0xD8, 0x0A, 0x00, 0x00, 0xDC, 0x0A, 0x00]);
+        let cases = [
+            (utf16(text, false), Encoding::Utf16Le),
+            (utf16(text, true), Encoding::Utf16Be),
+            (b"caf\xe9\n".to_vec(), Encoding::Latin1),
+            (text.as_bytes().to_vec(), Encoding::Utf8),
+            (lone, Encoding::Utf16Le),
+        ];
+        for (bytes, encoding) in cases {
+            // one byte at a time, so every char is split
+            let mut len = Utf8Len::new(encoding, 0);
+            for b in &bytes {
+                len.count(std::slice::from_ref(b));
+            }
+            let decoded = decode_all(&bytes, Some(encoding));
+            assert_eq!(decoded.len() as u64, len.len, "{decoded:?}");
+        }
+    }
+
+    #[test]
+    fn whole_files_round_trip() {
+        let mut le = vec![0xFF, 0xFE];
+        le.extend(utf16("Größe 🦀\n", false));
+        assert_eq!((Encoding::Utf16Le, 2), detect(&le, None));
+        let text = decode_exact(&le[2..], Encoding::Utf16Le).unwrap();
+        assert_eq!("Größe 🦀\n", text);
+        assert_eq!(le[2..], encode(&text, Encoding::Utf16Le).unwrap()[..]);
+        // a BOM of another encoding stays in the text
+        assert_eq!((Encoding::Latin1, 0), detect(&le, Some(Encoding::Latin1)));
+
+        assert_eq!(
+            Some("café".to_string()),
+            decode_exact(b"caf\xe9", Encoding::Latin1)
+        );
+        assert_eq!(Some(b"caf\xe9".to_vec()), encode("café", Encoding::Latin1));
+        assert_eq!(None, encode("🦀", Encoding::Latin1));
+        // a lone surrogate, or half a unit, can not be written back
+        assert_eq!(None, decode_exact(&[0x00, 0xD8], Encoding::Utf16Le));
+        assert_eq!(None, decode_exact(&[0x61], Encoding::Utf16Le));
+    }
+}
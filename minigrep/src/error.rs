@@ -0,0 +1,93 @@
+// every way minigrep can fail, instead of a Box<dyn Error> or a bare &str
+// main turns all of them into exit status 2, like grep does
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum MinigrepError {
+    // the command line did not make sense, the String says why
+    BadArgs(String),
+    // a file (or stdin, or stdout) could not be read or written
+    Io { path: String, source: io::Error },
+    // the query is not a valid pattern, the String says why
+    InvalidPattern(String),
+    // the file is not valid UTF-8 text
+    InvalidUtf8 { path: String },
+    // some files of a directory could not be searched, their errors were already printed
+    Incomplete { failed: usize },
+}
+
+impl MinigrepError {
+    // an io::Error that happened while reading or writing path
+    pub fn io(path: &str, source: io::Error) -> MinigrepError {
+        MinigrepError::Io {
+            path: path.to_string(),
+            source,
+        }
+    }
+
+    // the same while the lines of path are read as text (read_line, read_to_string), only
+    // there InvalidData means the bytes were not UTF-8, so it gets its own variant
+    pub fn text(path: &str, source: io::Error) -> MinigrepError {
+        if source.kind() == io::ErrorKind::InvalidData {
+            MinigrepError::InvalidUtf8 {
+                path: path.to_string(),
+            }
+        } else {
+            MinigrepError::io(path, source)
+        }
+    }
+}
+
+impl fmt::Display for MinigrepError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MinigrepError::BadArgs(message) => write!(f, "{}", message),
+            MinigrepError::Io { path, source } => write!(f, "{}: {}", path, source),
+            MinigrepError::InvalidPattern(e) => write!(f, "invalid pattern: {}", e),
+            MinigrepError::InvalidUtf8 { path } => write!(f, "{}: file is not valid UTF-8", path),
+            MinigrepError::Incomplete { failed: 1 } => write!(f, "1 file could not be searched"),
+            MinigrepError::Incomplete { failed } => {
+                write!(f, "{} files could not be searched", failed)
+            }
+        }
+    }
+}
+
+impl Error for MinigrepError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MinigrepError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+// lets us use ? on Matcher::new and build_regex
+impl From<regex::Error> for MinigrepError {
+    fn from(e: regex::Error) -> MinigrepError {
+        MinigrepError::InvalidPattern(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_data_is_invalid_utf8() {
+        let e = MinigrepError::text("a.bin", io::Error::new(io::ErrorKind::InvalidData, "bad"));
+        assert!(matches!(e, MinigrepError::InvalidUtf8 { .. }));
+        assert_eq!("a.bin: file is not valid UTF-8", e.to_string());
+
+        // outside of reading lines InvalidData can mean anything, its own message is kept
+        let damaged = io::Error::new(io::ErrorKind::InvalidData, "the index is damaged");
+        let e = MinigrepError::io(".minigrep-index", damaged);
+        assert_eq!(".minigrep-index: the index is damaged", e.to_string());
+
+        let e = MinigrepError::io("gone.txt", io::Error::from(io::ErrorKind::NotFound));
+        assert!(matches!(e, MinigrepError::Io { .. }));
+        assert!(e.to_string().starts_with("gone.txt: "));
+    }
+}
@@ -0,0 +1,163 @@
+// case insensitive matching without lowercasing every line into a new String
+// both sides are compared one char at a time after simple case folding, which maps every char
+// to exactly one char, so a match always covers whole chars of the original line
+use std::ops::Range;
+use std::sync::OnceLock;
+
+// the simple case fold of one char, like the C and S entries of Unicode's CaseFolding.txt
+// the standard library has no fold, but lowercasing the uppercase gets us the same classes:
+// ς and σ both become σ, ſ becomes s and the Kelvin sign becomes k
+pub fn fold_char(c: char) -> char {
+    if c.is_ascii() {
+        return c.to_ascii_lowercase();
+    }
+    // to_uppercase and to_lowercase search big tables, so for the first 65536 chars
+    // (every living script) we look the fold up once and remember it
+    match u16::try_from(c as u32) {
+        Ok(i) => char::from_u32(bmp_table()[i as usize] as u32).unwrap_or(c),
+        Err(_) => slow_fold(c),
+    }
+}
+
+fn bmp_table() -> &'static [u16] {
+    static TABLE: OnceLock<Box<[u16]>> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        (0..=u16::MAX)
+            .map(|i| match char::from_u32(i as u32) {
+                // the fold of a char below 65536 is below 65536 as well
+                Some(c) => u16::try_from(slow_fold(c) as u32).unwrap_or(i),
+                None => i, // surrogates are not chars
+            })
+            .collect()
+    })
+}
+
+fn slow_fold(c: char) -> char {
+    // the Turkish dotless ı has no fold, its uppercase I belongs to the plain i
+    if c == 'ı' {
+        return c;
+    }
+    // a char whose upper or lower case is more than one char (ß is SS, İ is i + a dot)
+    // has no simple fold for that step and stays as it is, ẞ still folds to ß this way
+    let upper = single(c.to_uppercase()).unwrap_or(c);
+    single(upper.to_lowercase()).unwrap_or(upper)
+}
+
+fn single(mut chars: impl Iterator<Item = char>) -> Option<char> {
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+// folds the query once, before searching
+pub fn fold(s: &str) -> String {
+    s.chars().map(fold_char).collect()
+}
+
+// needle has to be folded already
+pub fn contains(haystack: &str, needle: &str) -> bool {
+    find_at(haystack, needle, 0).is_some()
+}
+
+// byte ranges in haystack of every match of the folded needle, they never overlap
+pub fn find_spans(haystack: &str, needle: &str) -> Vec<Range<usize>> {
+    if needle.is_empty() {
+        return std::iter::once(0..0).collect();
+    }
+    let mut spans = Vec::new();
+    let mut start = 0;
+    while let Some(span) = find_at(haystack, needle, start) {
+        start = span.end;
+        spans.push(span);
+    }
+    spans
+}
+
+// the first match at or after byte start
+fn find_at(haystack: &str, needle: &str, start: usize) -> Option<Range<usize>> {
+    let Some(first) = needle.chars().next() else {
+        return Some(start..start); // an empty needle matches everywhere
+    };
+    let rest = &haystack[start..];
+    let matched = |i: usize| match_len(&rest[i..], needle).map(|len| start + i..start + i + len);
+
+    if first.is_ascii() {
+        // a match can only start at one of a few bytes, memchr finds those much faster than
+        // we could fold every char: the two ASCII cases plus the first byte of ſ and the Kelvin
+        // sign, the only chars outside ASCII that fold into it (a test below checks that)
+        let (lower, upper) = (first as u8, first.to_ascii_uppercase() as u8);
+        let other = match first {
+            's' => 0xC5, // ſ is C5 BF
+            'k' => 0xE2, // K is E2 84 AA
+            _ => upper,
+        };
+        return memchr::memchr3_iter(lower, upper, other, rest.as_bytes()).find_map(matched);
+    }
+
+    // only a char outside ASCII can fold into a char outside ASCII, so skip the ASCII in front
+    // is_ascii looks at a whole word at a time, a line of plain ASCII is done with right away
+    if rest.is_ascii() {
+        return None;
+    }
+    let skip = rest.bytes().position(|b| !b.is_ascii())?;
+    rest[skip..]
+        .char_indices()
+        .filter(|&(_, c)| fold_char(c) == first)
+        .find_map(|(i, _)| matched(skip + i))
+}
+
+// if haystack starts with the needle, how many bytes of haystack the match covers
+fn match_len(haystack: &str, needle: &str) -> Option<usize> {
+    let mut chars = haystack.char_indices();
+    for wanted in needle.chars() {
+        let (_, c) = chars.next()?;
+        if fold_char(c) != wanted {
+            return None;
+        }
+    }
+    Some(chars.next().map_or(haystack.len(), |(i, _)| i))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn folds_one_char_to_one_char() {
+        assert_eq!('a', fold_char('A'));
+        assert_eq!('σ', fold_char('Σ'));
+        assert_eq!('σ', fold_char('ς'));
+        assert_eq!('ß', fold_char('ẞ'));
+        assert_eq!('ß', fold_char('ß'));
+        assert_eq!('s', fold_char('ſ'));
+        assert_eq!('k', fold_char('\u{212A}')); // Kelvin sign
+        assert_eq!('İ', fold_char('İ'));
+        assert_eq!('ı', fold_char('ı'));
+        assert_eq!('i', fold_char('I'));
+        assert_eq!('\u{10428}', fold_char('\u{10400}')); // Deseret, above the table
+    }
+
+    #[test]
+    fn only_long_s_and_kelvin_fold_into_ascii() {
+        // find_at depends on this to jump straight to possible matches
+        let into_ascii: Vec<char> = (0x80..=0x10FFFF)
+            .filter_map(char::from_u32)
+            .filter(|&c| fold_char(c).is_ascii())
+            .collect();
+        assert_eq!(vec!['\u{17F}', '\u{212A}'], into_ascii);
+    }
+
+    #[test]
+    fn spans_cover_whole_chars() {
+        assert_eq!(vec![0..5, 6..11], find_spans("Hello hello", "hello"));
+        // ẞ is 3 bytes, its fold ß only 2
+        assert_eq!(
+            vec![0..6, 7..12],
+            find_spans("GRO\u{1E9E} groß", &fold("GRO\u{1E9E}"))
+        );
+        assert_eq!(vec![2..9], find_spans("İstanbul", "stanbul"));
+        assert!(!contains("İstanbul", "istanbul"));
+        assert!(find_spans("abc", "x").is_empty());
+    }
+}
@@ -0,0 +1,287 @@
+// --fuzzy N: lines with a substring at most N edits (Levenshtein distance) away from the query
+// Myers' bit-parallel algorithm keeps a whole column of the edit distance table in a few u64s,
+// so every char of the line costs a handful of bit operations instead of a loop over the query
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::fold::fold_char;
+use crate::MinigrepError;
+
+// one bit per char of the query has to fit in a u64
+pub const MAX_LEN: usize = 64;
+
+// one match in a line: where it is and how many edits it is away from the query
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuzzyMatch {
+    pub span: Range<usize>,
+    pub distance: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Fuzzy {
+    len: usize,
+    max_distance: u32,
+    case_sensitive: bool,
+    // for the query and the query backwards (used to find where a match starts)
+    forward: Positions,
+    backward: Positions,
+}
+
+impl Fuzzy {
+    pub fn new(
+        query: &str,
+        max_distance: usize,
+        case_sensitive: bool,
+    ) -> Result<Fuzzy, MinigrepError> {
+        let fold = |c: char| if case_sensitive { c } else { fold_char(c) };
+        let chars: Vec<char> = query.chars().map(fold).collect();
+        if chars.len() > MAX_LEN {
+            return Err(MinigrepError::InvalidPattern(format!(
+                "--fuzzy queries can be at most {} chars long",
+                MAX_LEN
+            )));
+        }
+        Ok(Fuzzy {
+            len: chars.len(),
+            // more edits than the query is long would match everything anyway
+            max_distance: max_distance.min(chars.len()) as u32,
+            case_sensitive,
+            forward: Positions::new(chars.iter().copied()),
+            backward: Positions::new(chars.iter().rev().copied()),
+        })
+    }
+
+    fn fold(&self, c: char) -> char {
+        if self.case_sensitive {
+            c
+        } else {
+            fold_char(c)
+        }
+    }
+
+    pub fn is_match(&self, line: &str) -> bool {
+        if self.len == 0 {
+            return true;
+        }
+        let mut column = Column::new(self.len);
+        line.chars()
+            .any(|c| column.step(self.forward.get(self.fold(c)), false) <= self.max_distance)
+    }
+
+    // every match in the line from left to right, they never overlap
+    // where the distance is low for a few chars in a row only the best end is kept (the last of
+    // equally good ones, so colour and not colou matches color), the start is then the closest
+    // one with that distance
+    pub fn find(&self, line: &str) -> Vec<FuzzyMatch> {
+        if self.len == 0 {
+            return vec![FuzzyMatch {
+                span: 0..0,
+                distance: 0,
+            }];
+        }
+        let mut found = Vec::new();
+        let mut column = Column::new(self.len);
+        let mut previous = self.len as u32;
+        // an end we keep until we know the next char does not make it better
+        let mut candidate: Option<(usize, u32)> = None;
+        for (i, c) in line.char_indices() {
+            let distance = column.step(self.forward.get(self.fold(c)), false);
+            if let Some((end, best)) = candidate.take() {
+                if best < distance {
+                    self.push(line, end, best, &mut found);
+                }
+            }
+            if distance <= self.max_distance && distance <= previous {
+                candidate = Some((i + c.len_utf8(), distance));
+            }
+            previous = distance;
+        }
+        if let Some((end, best)) = candidate {
+            self.push(line, end, best, &mut found);
+        }
+        found
+    }
+
+    // the query backwards against the line backwards from end, the first time the distance
+    // comes down to the one we found is the shortest substring that is that close
+    fn push(&self, line: &str, end: usize, distance: u32, found: &mut Vec<FuzzyMatch>) {
+        let mut start = end;
+        if distance < self.len as u32 {
+            let mut column = Column::new(self.len);
+            for (i, c) in line[..end].char_indices().rev() {
+                if column.step(self.backward.get(self.fold(c)), true) == distance {
+                    start = i;
+                    break;
+                }
+            }
+        }
+        if found.last().is_some_and(|last| start < last.span.end) {
+            return; // it overlaps the match before, that one was found first
+        }
+        found.push(FuzzyMatch {
+            span: start..end,
+            distance,
+        });
+    }
+
+    // the edit distance between the query and the whole of text, for printing a match
+    pub fn distance(&self, text: &str) -> u32 {
+        if self.len == 0 {
+            return text.chars().count() as u32;
+        }
+        let mut column = Column::new(self.len);
+        let mut distance = self.len as u32;
+        for c in text.chars() {
+            distance = column.step(self.forward.get(self.fold(c)), true);
+        }
+        distance
+    }
+}
+
+// the bits of the places every char is at in the query (Peq in Myers' paper)
+#[derive(Debug, Clone)]
+struct Positions {
+    // boxed, so a Matcher stays small
+    ascii: Box<[u64; 128]>,
+    other: HashMap<char, u64>,
+}
+
+impl Positions {
+    fn new(chars: impl Iterator<Item = char>) -> Positions {
+        let mut positions = Positions {
+            ascii: Box::new([0; 128]),
+            other: HashMap::new(),
+        };
+        for (i, c) in chars.enumerate() {
+            if c.is_ascii() {
+                positions.ascii[c as usize] |= 1 << i;
+            } else {
+                *positions.other.entry(c).or_insert(0) |= 1 << i;
+            }
+        }
+        positions
+    }
+
+    fn get(&self, c: char) -> u64 {
+        if c.is_ascii() {
+            self.ascii[c as usize]
+        } else {
+            self.other.get(&c).copied().unwrap_or(0)
+        }
+    }
+}
+
+// one column of the edit distance table between the query and the text read so far,
+// stored as +1/-1 differences between the rows (Pv and Mv) plus the value of the last row
+struct Column {
+    pv: u64,
+    mv: u64,
+    last: u64,
+    distance: u32,
+}
+
+impl Column {
+    fn new(len: usize) -> Column {
+        Column {
+            pv: !0,
+            mv: 0,
+            last: 1 << (len - 1),
+            distance: len as u32,
+        }
+    }
+
+    // reads one more char of the text, eq is where that char is in the query
+    // anchored means the match has to start at the first char we read, otherwise
+    // it can start anywhere, which is what searching inside a line needs
+    fn step(&mut self, eq: u64, anchored: bool) -> u32 {
+        let xv = eq | self.mv;
+        let xh = ((eq & self.pv).wrapping_add(self.pv) ^ self.pv) | eq;
+        let ph = self.mv | !(xh | self.pv);
+        let mh = self.pv & xh;
+        if ph & self.last != 0 {
+            self.distance += 1;
+        } else if mh & self.last != 0 {
+            self.distance -= 1;
+        }
+        let ph = (ph << 1) | anchored as u64;
+        let mh = mh << 1;
+        self.pv = mh | !(xv | ph);
+        self.mv = ph & xv;
+        self.distance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the plain dynamic programming version, to check the bit tricks against
+    fn levenshtein(a: &str, b: &str) -> u32 {
+        let b: Vec<char> = b.chars().collect();
+        let mut row: Vec<u32> = (0..=b.len() as u32).collect();
+        for (i, ca) in a.chars().enumerate() {
+            let mut diagonal = row[0];
+            row[0] = i as u32 + 1;
+            for j in 0..b.len() {
+                let substitute = diagonal + (ca != b[j]) as u32;
+                diagonal = row[j + 1];
+                row[j + 1] = substitute.min(row[j] + 1).min(row[j + 1] + 1);
+            }
+        }
+        row[b.len()]
+    }
+
+    fn matches<'a>(query: &str, k: usize, line: &'a str) -> Vec<(&'a str, u32)> {
+        let fuzzy = Fuzzy::new(query, k, false).unwrap();
+        fuzzy
+            .find(line)
+            .into_iter()
+            .map(|m| (&line[m.span], m.distance))
+            .collect()
+    }
+
+    #[test]
+    fn finds_misspellings() {
+        assert_eq!(vec![("colour", 1)], matches("color", 1, "the colour red"));
+        assert_eq!(
+            vec![("Jonson", 1)],
+            matches("johnson", 1, "ticket from Jonson")
+        );
+        assert_eq!(
+            vec![("conection", 1), ("CONNECTION", 0)],
+            matches("connection", 2, "conection lost, CONNECTION reset")
+        );
+        assert_eq!(vec![("Müller", 1)], matches("müler", 1, "Herr Müller"));
+        assert!(matches("timeout", 1, "time is out").is_empty());
+        assert!(Fuzzy::new(&"x".repeat(65), 1, true).is_err());
+    }
+
+    #[test]
+    fn agrees_with_plain_levenshtein() {
+        let words = [
+            "",
+            "a",
+            "kitten",
+            "sitting",
+            "flaw",
+            "lawn",
+            "intention",
+            "execution",
+            "straße",
+        ];
+        for a in words {
+            for b in words {
+                let fuzzy = Fuzzy::new(a, 0, true).unwrap();
+                assert_eq!(levenshtein(a, b), fuzzy.distance(b), "{a} / {b}");
+            }
+        }
+
+        // every match really is that close
+        let line = "sitting kitten mitten bitten";
+        let fuzzy = Fuzzy::new("kitten", 2, true).unwrap();
+        for m in fuzzy.find(line) {
+            assert_eq!(levenshtein("kitten", &line[m.span.clone()]), m.distance);
+        }
+        assert_eq!(4, fuzzy.find(line).len());
+    }
+}
@@ -0,0 +1,155 @@
+// shell style globs like *.rs, src/**/*.txt or {a,b}.md
+// we translate them into a regex once and let the regex crate do the matching
+use regex::Regex;
+
+use crate::MinigrepError;
+
+#[derive(Debug, Clone)]
+pub struct Glob {
+    re: Regex,
+    // a glob without a / (like *.rs) is matched against the file name only, anywhere in the tree
+    basename_only: bool,
+}
+
+impl Glob {
+    pub fn new(glob: &str) -> Result<Glob, MinigrepError> {
+        Glob::build(glob, !glob.contains('/'))
+    }
+
+    // always matched against the whole path, like /TODO in a .gitignore only means the top level one
+    pub fn anchored(glob: &str) -> Result<Glob, MinigrepError> {
+        Glob::build(glob, false)
+    }
+
+    fn build(glob: &str, basename_only: bool) -> Result<Glob, MinigrepError> {
+        let re = Regex::new(&format!("^{}$", to_regex(glob)?))
+            .map_err(|e| MinigrepError::InvalidPattern(format!("glob '{}': {}", glob, e)))?;
+        Ok(Glob { re, basename_only })
+    }
+
+    // path is relative to where the search started and always uses / between the parts
+    pub fn is_match(&self, path: &str) -> bool {
+        if self.basename_only {
+            let name = path.trim_end_matches('/');
+            let name = name.rsplit('/').next().unwrap_or(name);
+            self.re.is_match(name)
+        } else {
+            self.re.is_match(path)
+        }
+    }
+}
+
+// * and ? never cross a /, ** does
+fn to_regex(glob: &str) -> Result<String, MinigrepError> {
+    let invalid = |why: &str| MinigrepError::InvalidPattern(format!("glob '{}': {}", glob, why));
+    let chars: Vec<char> = glob.chars().collect();
+    let mut re = String::new();
+    // inside {a,b} a comma ends one alternative
+    let mut braces = 0;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                let at_start = i == 0 || chars[i - 1] == '/';
+                let slash_after = chars.get(i + 2) == Some(&'/');
+                if at_start && slash_after {
+                    // **/ matches zero or more directories
+                    re.push_str("(?:.*/)?");
+                    i += 3;
+                } else {
+                    // a trailing ** (or ** in the middle of a name) matches anything
+                    re.push_str(".*");
+                    i += 2;
+                }
+                continue;
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            '[' => {
+                // copy the class, ! at the start means "not" like ^ does in a regex
+                let end = chars[i + 1..]
+                    .iter()
+                    .skip(1) // a ] right after [ is part of the class
+                    .position(|&c| c == ']')
+                    .map(|p| i + 2 + p)
+                    .ok_or_else(|| invalid("unclosed ["))?;
+                re.push('[');
+                let mut class = &chars[i + 1..end];
+                if let Some('!') | Some('^') = class.first() {
+                    re.push('^');
+                    class = &class[1..];
+                }
+                for &c in class {
+                    if c == '\\' || c == '[' || c == '&' || c == '~' {
+                        re.push('\\');
+                    }
+                    re.push(c);
+                }
+                re.push(']');
+                i = end;
+            }
+            '{' => {
+                braces += 1;
+                re.push_str("(?:");
+            }
+            '}' if braces > 0 => {
+                braces -= 1;
+                re.push(')');
+            }
+            ',' if braces > 0 => re.push('|'),
+            '\\' => {
+                // an escaped char is taken literally
+                i += 1;
+                let next = chars.get(i).ok_or_else(|| invalid("trailing \\"))?;
+                re.push_str(&regex::escape(&next.to_string()));
+            }
+            _ => re.push_str(&regex::escape(&c.to_string())),
+        }
+        i += 1;
+    }
+
+    if braces > 0 {
+        return Err(invalid("unclosed {"));
+    }
+    Ok(re)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_match(glob: &str, path: &str) -> bool {
+        Glob::new(glob).unwrap().is_match(path)
+    }
+
+    #[test]
+    fn stars() {
+        assert!(is_match("*.rs", "src/lib.rs"));
+        assert!(is_match("*.rs", "lib.rs"));
+        assert!(!is_match("*.rs", "lib.rs.bak"));
+        assert!(is_match("src/*.rs", "src/lib.rs"));
+        assert!(!is_match("src/*.rs", "src/bin/main.rs"));
+        assert!(is_match("src/**/*.rs", "src/bin/main.rs"));
+        assert!(is_match("src/**/*.rs", "src/main.rs"));
+        assert!(is_match("**/tests/*.rs", "tests/cli.rs"));
+        assert!(is_match("target/**", "target/debug/minigrep"));
+        assert!(is_match("target/**", "target/"));
+        assert!(!is_match("target/**", "src/target.rs"));
+    }
+
+    #[test]
+    fn classes_braces_and_escapes() {
+        assert!(is_match("file?.txt", "file1.txt"));
+        assert!(!is_match("file?.txt", "file10.txt"));
+        assert!(is_match("[a-c]*.md", "b.md"));
+        assert!(!is_match("[!a-c]*.md", "b.md"));
+        assert!(is_match("*.{rs,toml}", "Cargo.toml"));
+        assert!(!is_match("*.{rs,toml}", "Cargo.lock"));
+        assert!(is_match(r"\*.txt", "*.txt"));
+        assert!(!is_match(r"\*.txt", "a.txt"));
+        assert!(Glob::new("{a,b").is_err());
+        assert!(Glob::new("[abc").is_err());
+    }
+}
@@ -0,0 +1,232 @@
+// rules from .gitignore and .ignore files, and the --glob / --type filters from the command line
+use std::fs;
+use std::path::Path;
+
+use crate::glob::Glob;
+use crate::MinigrepError;
+
+// the ignore files we read in every directory, later ones win over earlier ones
+pub const IGNORE_FILES: &[&str] = &[".gitignore", ".ignore"];
+
+struct Rule {
+    glob: Glob,
+    // a ! in front of the pattern, the path is not ignored after all
+    negated: bool,
+    // a / at the end of the pattern, it only matches directories
+    dir_only: bool,
+}
+
+// the rules of all ignore files in one directory
+pub struct IgnoreRules {
+    rules: Vec<Rule>,
+}
+
+impl IgnoreRules {
+    // reads the ignore files of dir, returns None when there are none so the walker can skip it
+    pub fn from_dir(dir: &Path) -> Option<IgnoreRules> {
+        let mut rules = IgnoreRules { rules: Vec::new() };
+        for name in IGNORE_FILES {
+            if let Ok(contents) = fs::read_to_string(dir.join(name)) {
+                rules.add_lines(&contents);
+            }
+        }
+        (!rules.rules.is_empty()).then_some(rules)
+    }
+
+    pub fn parse(contents: &str) -> IgnoreRules {
+        let mut rules = IgnoreRules { rules: Vec::new() };
+        rules.add_lines(contents);
+        rules
+    }
+
+    // the same format as .gitignore: one pattern per line, # starts a comment, ! negates,
+    // a / at the end means directories only and a / anywhere else ties the pattern to this directory
+    fn add_lines(&mut self, contents: &str) {
+        for line in contents.lines() {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (negated, line) = match line.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, line.strip_prefix('\\').unwrap_or(line)),
+            };
+            let (dir_only, line) = match line.strip_suffix('/') {
+                Some(rest) => (true, rest),
+                None => (false, line),
+            };
+
+            // without any / the pattern matches a name at any depth, Glob::new does that for us
+            let glob = match line.strip_prefix('/') {
+                Some(anchored) => Glob::anchored(anchored),
+                None => Glob::new(line),
+            };
+            // a broken line is skipped like git does, it should not stop the search
+            if let Ok(glob) = glob {
+                self.rules.push(Rule {
+                    glob,
+                    negated,
+                    dir_only,
+                });
+            }
+        }
+    }
+
+    // path is relative to the directory of the ignore file
+    // Some(true) means ignored, Some(false) means a ! rule let it through, None means no rule matched
+    pub fn matched(&self, path: &str, is_dir: bool) -> Option<bool> {
+        // the last matching rule wins
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| (is_dir || !rule.dir_only) && rule.glob.is_match(path))
+            .map(|rule| !rule.negated)
+    }
+}
+
+// what --glob and --type leave in, for files found while walking a directory
+// files given directly on the command line are always searched
+#[derive(Default)]
+pub struct FileFilter {
+    // (glob, exclude) in the order they were given, the last matching one wins
+    globs: Vec<(Glob, bool)>,
+    has_includes: bool,
+    types: Vec<Glob>,
+    types_not: Vec<Glob>,
+}
+
+// names for --type, the same idea as ripgrep's file types
+pub const FILE_TYPES: &[(&str, &[&str])] = &[
+    ("c", &["*.c", "*.h"]),
+    ("cpp", &["*.cpp", "*.cc", "*.cxx", "*.hpp", "*.hh", "*.hxx"]),
+    ("css", &["*.css", "*.scss"]),
+    ("go", &["*.go"]),
+    ("html", &["*.html", "*.htm"]),
+    ("java", &["*.java"]),
+    ("js", &["*.js", "*.mjs", "*.cjs", "*.jsx"]),
+    ("json", &["*.json"]),
+    ("markdown", &["*.md", "*.markdown"]),
+    ("md", &["*.md", "*.markdown"]),
+    ("py", &["*.py", "*.pyi"]),
+    ("python", &["*.py", "*.pyi"]),
+    ("rust", &["*.rs"]),
+    ("sh", &["*.sh", "*.bash", "*.zsh"]),
+    ("toml", &["*.toml"]),
+    ("ts", &["*.ts", "*.tsx"]),
+    ("txt", &["*.txt"]),
+    ("yaml", &["*.yaml", "*.yml"]),
+];
+
+impl FileFilter {
+    // globs starting with ! exclude, the others include
+    pub fn new(
+        globs: &[String],
+        types: &[String],
+        types_not: &[String],
+    ) -> Result<FileFilter, MinigrepError> {
+        let mut filter = FileFilter::default();
+        for glob in globs {
+            let (exclude, glob) = match glob.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, glob.as_str()),
+            };
+            filter.has_includes |= !exclude;
+            filter.globs.push((Glob::new(glob)?, exclude));
+        }
+        filter.types = type_globs(types)?;
+        filter.types_not = type_globs(types_not)?;
+        Ok(filter)
+    }
+
+    // path is relative to the directory we started in
+    pub fn is_dir_excluded(&self, path: &str) -> bool {
+        // a directory is only skipped by an exclude glob, like !target/** or !target/
+        let with_slash = format!("{}/", path);
+        self.globs
+            .iter()
+            .rev()
+            .find(|(glob, _)| glob.is_match(&with_slash) || glob.is_match(path))
+            .is_some_and(|(_, exclude)| *exclude)
+    }
+
+    pub fn is_file_selected(&self, path: &str) -> bool {
+        match self
+            .globs
+            .iter()
+            .rev()
+            .find(|(glob, _)| glob.is_match(path))
+        {
+            Some((_, exclude)) if *exclude => return false,
+            Some(_) => {}
+            // with any include glob, a file has to match one of them
+            None if self.has_includes => return false,
+            None => {}
+        }
+        if !self.types.is_empty() && !self.types.iter().any(|glob| glob.is_match(path)) {
+            return false;
+        }
+        !self.types_not.iter().any(|glob| glob.is_match(path))
+    }
+}
+
+fn type_globs(names: &[String]) -> Result<Vec<Glob>, MinigrepError> {
+    let mut globs = Vec::new();
+    for name in names {
+        let (_, patterns) = FILE_TYPES
+            .iter()
+            .find(|(known, _)| known == name)
+            .ok_or_else(|| {
+                let known: Vec<&str> = FILE_TYPES.iter().map(|(name, _)| *name).collect();
+                MinigrepError::BadArgs(format!(
+                    "unknown file type '{}', known types are: {}",
+                    name,
+                    known.join(", ")
+                ))
+            })?;
+        for pattern in patterns.iter() {
+            globs.push(Glob::new(pattern)?);
+        }
+    }
+    Ok(globs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gitignore_rules() {
+        let rules =
+            IgnoreRules::parse("# build output\ntarget/\n*.log\n!keep.log\n/TODO\ndocs/*.html\n");
+        assert_eq!(Some(true), rules.matched("target", true));
+        assert_eq!(None, rules.matched("target", false)); // a file called target is fine
+        assert_eq!(Some(true), rules.matched("logs/run.log", false));
+        assert_eq!(Some(false), rules.matched("logs/keep.log", false));
+        assert_eq!(Some(true), rules.matched("TODO", false));
+        assert_eq!(None, rules.matched("src/TODO", false));
+        assert_eq!(Some(true), rules.matched("docs/index.html", false));
+        assert_eq!(None, rules.matched("src/docs/index.html", false));
+        assert_eq!(None, rules.matched("src/main.rs", false));
+    }
+
+    #[test]
+    fn globs_and_types() {
+        let globs = |globs: &[&str]| globs.iter().map(|g| g.to_string()).collect::<Vec<_>>();
+        let filter = FileFilter::new(&globs(&["*.rs", "!target/**"]), &[], &[]).unwrap();
+        assert!(filter.is_file_selected("src/lib.rs"));
+        assert!(!filter.is_file_selected("Cargo.toml"));
+        assert!(filter.is_dir_excluded("target"));
+        assert!(!filter.is_dir_excluded("src"));
+
+        // the last matching glob wins
+        let filter = FileFilter::new(&globs(&["!*.txt", "poem.txt"]), &[], &[]).unwrap();
+        assert!(filter.is_file_selected("poem.txt"));
+        assert!(!filter.is_file_selected("notes.txt"));
+
+        let filter = FileFilter::new(&[], &globs(&["rust", "toml"]), &globs(&["md"])).unwrap();
+        assert!(filter.is_file_selected("src/main.rs"));
+        assert!(filter.is_file_selected("Cargo.toml"));
+        assert!(!filter.is_file_selected("README.md"));
+        assert!(FileFilter::new(&[], &globs(&["cobol"]), &[]).is_err());
+    }
+}
@@ -0,0 +1,706 @@
+// minigrep index DIR and search --index: a trigram index of a directory, so searching the same
+// big tree again and again only has to read the files that can have a match
+// for every file we keep the set of 3 byte sequences (trigrams) in its case folded text, a file
+// that lacks a trigram of the query can not match it, every other file is still searched as
+// usual, so the index only ever makes a search faster and never changes what it finds
+//
+// the file is DIR/.minigrep-index, every number is a LEB128 varint unless it says otherwise:
+//
+//     MAGIC, VERSION as 4 bytes little endian, number of files, then for every file:
+//     path length, path (UTF-8, relative to DIR, / between the parts), size, mtime seconds,
+//     mtime nanoseconds, number of trigrams, the sorted trigrams as differences to the one before
+//
+// a file whose size or mtime changed since it was indexed is searched, minigrep index reads it again
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::fs::{self, File, Metadata};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::thread;
+use std::time::UNIX_EPOCH;
+
+use regex_syntax::hir::{Class, Hir, HirKind};
+
+use crate::query::Query;
+use crate::walk::{self, WalkOptions};
+use crate::{encoding, fold, pool, Config, MinigrepError};
+
+pub const INDEX_FILE: &str = ".minigrep-index";
+const MAGIC: &[u8; 8] = b"MGINDEX\0";
+// bump this whenever the format (or what goes into a trigram) changes
+pub const VERSION: u32 = 1;
+
+// what minigrep index was asked to do
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IndexOptions {
+    pub dir: PathBuf,
+    // the same as for a search, which files the walk finds
+    pub hidden: bool,
+    pub no_ignore: bool,
+}
+
+// size and mtime, when both are the same the file did not change
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Stamp {
+    size: u64,
+    secs: u64,
+    nanos: u32,
+}
+
+impl Stamp {
+    fn of(metadata: &Metadata) -> Stamp {
+        let mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|mtime| mtime.duration_since(UNIX_EPOCH).ok())
+            .unwrap_or_default();
+        Stamp {
+            size: metadata.len(),
+            secs: mtime.as_secs(),
+            nanos: mtime.subsec_nanos(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    stamp: Stamp,
+    // sorted, every trigram is 3 bytes in the low bits
+    trigrams: Vec<u32>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Index {
+    // by path relative to the directory, sorted so the file comes out the same every time
+    entries: BTreeMap<String, Entry>,
+}
+
+impl Index {
+    pub fn path(dir: &Path) -> PathBuf {
+        dir.join(INDEX_FILE)
+    }
+
+    pub fn load(dir: &Path) -> io::Result<Index> {
+        Index::decode(&fs::read(Index::path(dir))?)
+    }
+
+    // false only when file is in the index as it is now and it lacks what required needs
+    pub fn may_match(&self, dir: &Path, file: &Path, required: &Required) -> bool {
+        let Some(entry) = relative(dir, file).and_then(|rel| self.entries.get(&rel)) else {
+            return true; // a new file, nothing is known about it
+        };
+        match fs::metadata(file) {
+            Ok(metadata) if Stamp::of(&metadata) == entry.stamp => {
+                required.is_satisfied_by(&entry.trigrams)
+            }
+            _ => true,
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        put(&mut out, self.entries.len() as u64);
+        for (path, entry) in &self.entries {
+            put(&mut out, path.len() as u64);
+            out.extend_from_slice(path.as_bytes());
+            put(&mut out, entry.stamp.size);
+            put(&mut out, entry.stamp.secs);
+            put(&mut out, entry.stamp.nanos as u64);
+            put(&mut out, entry.trigrams.len() as u64);
+            let mut last = 0;
+            for &trigram in &entry.trigrams {
+                put(&mut out, (trigram - last) as u64);
+                last = trigram;
+            }
+        }
+        out
+    }
+
+    fn decode(bytes: &[u8]) -> io::Result<Index> {
+        let mut input = Reader { bytes, at: 0 };
+        if input.take(MAGIC.len())? != MAGIC {
+            return Err(io::Error::other("not a minigrep index"));
+        }
+        let version = u32::from_le_bytes(input.take(4)?.try_into().unwrap_or_default());
+        if version != VERSION {
+            return Err(io::Error::other(format!(
+                "index version {} is not {}, the version this minigrep reads",
+                version, VERSION
+            )));
+        }
+        let mut index = Index::default();
+        for _ in 0..input.number()? {
+            let len = input.number()? as usize;
+            let path = std::str::from_utf8(input.take(len)?)
+                .map_err(|_| corrupt())?
+                .to_string();
+            let stamp = Stamp {
+                size: input.number()?,
+                secs: input.number()?,
+                nanos: u32::try_from(input.number()?).map_err(|_| corrupt())?,
+            };
+            let count = input.number()? as usize;
+            // every trigram takes at least a byte, a bad count should not allocate gigabytes
+            let mut trigrams = Vec::with_capacity(count.min(bytes.len()));
+            let mut last = 0u64;
+            for _ in 0..count {
+                last = last.checked_add(input.number()?).ok_or_else(corrupt)?;
+                trigrams.push(u32::try_from(last).map_err(|_| corrupt())?);
+            }
+            index.entries.insert(path, Entry { stamp, trigrams });
+        }
+        if input.at != bytes.len() {
+            return Err(corrupt());
+        }
+        Ok(index)
+    }
+
+    // a temp file renamed over the old index, a search never sees half an index
+    fn save(&self, path: &Path) -> io::Result<()> {
+        let temp = path.with_file_name(format!("{}.tmp-{}", INDEX_FILE, process::id()));
+        let result = File::create(&temp)
+            .and_then(|mut file| file.write_all(&self.encode()))
+            .and_then(|_| fs::rename(&temp, path));
+        if result.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+        result
+    }
+}
+
+// our own index file is never indexed or searched, not even with --hidden
+pub fn is_index_file(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name == INDEX_FILE)
+}
+
+// the key of file in the index of dir, files with names that are not UTF-8 are left out
+fn relative(dir: &Path, file: &Path) -> Option<String> {
+    let parts = file
+        .strip_prefix(dir)
+        .ok()?
+        .components()
+        .map(|part| part.as_os_str().to_str())
+        .collect::<Option<Vec<_>>>()?;
+    Some(parts.join("/"))
+}
+
+fn put(out: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        out.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+fn corrupt() -> io::Error {
+    io::Error::other("the index is damaged, run minigrep index again")
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let taken = self.bytes.get(self.at..self.at.saturating_add(len));
+        self.at = self.at.saturating_add(len);
+        taken.ok_or_else(corrupt)
+    }
+
+    fn number(&mut self) -> io::Result<u64> {
+        let mut n = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            // the 10th byte only has room for the top bit, anything more does not fit a u64
+            if shift == 63 && byte > 1 {
+                return Err(corrupt());
+            }
+            n |= ((byte & 0x7f) as u64) << shift;
+            if byte < 0x80 {
+                return Ok(n);
+            }
+        }
+        Err(corrupt())
+    }
+}
+
+// what minigrep index did, printed when it is done
+#[derive(Debug, PartialEq)]
+pub struct Summary {
+    pub path: PathBuf,
+    pub files: usize,
+    // new files and files that changed
+    pub updated: usize,
+    pub removed: usize,
+    // the old index could not be read, so every file was indexed again
+    pub rebuilt: bool,
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} files, {} new or changed, {} removed",
+            self.path.display(),
+            self.files,
+            self.updated,
+            self.removed
+        )?;
+        if self.rebuilt {
+            write!(f, " (the old index could not be read and was rebuilt)")?;
+        }
+        Ok(())
+    }
+}
+
+// builds the index of options.dir, or brings it up to date: only files with another size or
+// mtime than last time are read again
+// a file that can not be read is reported and left out, like in a search
+pub fn update(options: &IndexOptions) -> Result<Summary, MinigrepError> {
+    let dir = &options.dir;
+    let path = Index::path(dir);
+    let name = path.display().to_string();
+    let (mut old, rebuilt) = match Index::load(dir) {
+        Ok(index) => (index, false),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => (Index::default(), false),
+        Err(_) => (Index::default(), true),
+    };
+
+    let walk_options = WalkOptions {
+        hidden: options.hidden,
+        ignore: !options.no_ignore,
+        ..WalkOptions::default()
+    };
+    let walk = walk::walk_with(dir, &walk_options)
+        .map_err(|e| MinigrepError::io(&dir.display().to_string(), e))?;
+    for (path, e) in walk.errors {
+        eprintln!("{}", MinigrepError::io(&path.display().to_string(), e));
+    }
+
+    let mut index = Index::default();
+    let mut changed = Vec::new();
+    for file in walk.files {
+        if is_index_file(&file) {
+            continue;
+        }
+        let Some(rel) = relative(dir, &file) else {
+            continue;
+        };
+        // the stamp is taken before reading, a change while we read is seen next time
+        let stamp = match fs::metadata(&file) {
+            Ok(metadata) => Stamp::of(&metadata),
+            Err(e) => {
+                eprintln!("{}", MinigrepError::io(&file.display().to_string(), e));
+                continue;
+            }
+        };
+        match old.entries.remove(&rel) {
+            Some(entry) if entry.stamp == stamp => {
+                index.entries.insert(rel, entry);
+            }
+            _ => changed.push((rel, file, stamp)),
+        }
+    }
+    // whatever is left was deleted (or is ignored now)
+    let removed = old.entries.len();
+
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let mut updated = 0;
+    pool::run_ordered(
+        changed.len(),
+        threads,
+        |i| trigrams_of(&changed[i].1),
+        |i, result| {
+            let (rel, file, stamp) = &changed[i];
+            match result {
+                Ok(trigrams) => {
+                    let entry = Entry {
+                        stamp: *stamp,
+                        trigrams,
+                    };
+                    index.entries.insert(rel.clone(), entry);
+                    updated += 1;
+                }
+                Err(e) => eprintln!("{}", MinigrepError::io(&file.display().to_string(), e)),
+            }
+            true
+        },
+    );
+
+    index.save(&path).map_err(|e| MinigrepError::io(&name, e))?;
+    Ok(Summary {
+        path,
+        files: index.entries.len(),
+        updated,
+        removed,
+        rebuilt,
+    })
+}
+
+// every trigram of the file as a search sees it: decoded from UTF-16 when it has a BOM, a �
+// for every byte that is not UTF-8, and case folded so one index works with and without -i
+// trigrams across line endings are in it too, for --multiline
+fn trigrams_of(path: &Path) -> io::Result<Vec<u32>> {
+    let mut reader = encoding::decode(BufReader::new(File::open(path)?), None)?;
+    // a small file has a few hundred trigrams, a set of them is much cheaper than 2^24 bits
+    let mut set = HashSet::new();
+    let mut buf = Vec::new();
+    let mut window = 0u32;
+    let mut seen = 0;
+    loop {
+        buf.clear();
+        if reader.read_until(b'\n', &mut buf)? == 0 {
+            break;
+        }
+        let line = fold::fold(&String::from_utf8_lossy(&buf));
+        for &byte in line.as_bytes() {
+            window = (window << 8 | byte as u32) & 0xff_ffff;
+            seen += 1;
+            if seen >= 3 {
+                set.insert(window);
+            }
+        }
+    }
+    let mut trigrams: Vec<u32> = set.into_iter().collect();
+    trigrams.sort_unstable();
+    Ok(trigrams)
+}
+
+// what a file needs to contain to have a chance of matching
+#[derive(Debug, Clone, PartialEq)]
+pub enum Required {
+    // anything could match, the index can not help
+    Any,
+    // every one of these trigrams
+    All(Vec<u32>),
+    And(Vec<Required>),
+    // Or(vec![]) is nothing at all, like an empty -f file
+    Or(Vec<Required>),
+}
+
+impl Required {
+    // for the patterns of a search
+    pub fn of(config: &Config) -> Required {
+        // -v selects the lines without a match, those can be in any file, and with --fuzzy
+        // a match does not have to contain the query at all
+        // with --encoding the file is read differently than the index read it
+        if config.invert || config.fuzzy.is_some() || config.encoding.is_some() {
+            return Required::Any;
+        }
+        let pattern = |pattern: &str| {
+            if config.regex {
+                Required::regex(pattern)
+            } else {
+                Required::literal(pattern)
+            }
+        };
+        if config.boolean_query.is_some() {
+            return match Query::parse(&config.patterns[0]) {
+                Ok(query) => query.required(pattern),
+                Err(_) => Required::Any,
+            };
+        }
+        // a line matches when any of the patterns does
+        Required::or(config.patterns.iter().map(|p| pattern(p)).collect())
+    }
+
+    // a fixed string, -w and -x only make the match longer than the string
+    pub fn literal(text: &str) -> Required {
+        let folded = fold::fold(text);
+        let mut trigrams: Vec<u32> = folded
+            .as_bytes()
+            .windows(3)
+            .map(|w| (w[0] as u32) << 16 | (w[1] as u32) << 8 | w[2] as u32)
+            .collect();
+        if trigrams.is_empty() {
+            return Required::Any; // shorter than 3 bytes, every file could have it
+        }
+        trigrams.sort_unstable();
+        trigrams.dedup();
+        Required::All(trigrams)
+    }
+
+    // the literal parts of a regex, a pattern the regex crate can not parse has no requirements
+    // (the search itself reports it)
+    pub fn regex(pattern: &str) -> Required {
+        match regex_syntax::parse(pattern) {
+            Ok(hir) => Literals::of(&hir).into_required(),
+            Err(_) => Required::Any,
+        }
+    }
+
+    pub fn and(parts: Vec<Required>) -> Required {
+        let mut parts: Vec<Required> = parts
+            .into_iter()
+            .filter(|part| *part != Required::Any)
+            .collect();
+        match parts.len() {
+            0 => Required::Any,
+            1 => parts.remove(0),
+            _ => Required::And(parts),
+        }
+    }
+
+    pub fn or(mut parts: Vec<Required>) -> Required {
+        if parts.contains(&Required::Any) {
+            return Required::Any;
+        }
+        match parts.len() {
+            1 => parts.remove(0),
+            _ => Required::Or(parts),
+        }
+    }
+
+    // trigrams is sorted
+    fn is_satisfied_by(&self, trigrams: &[u32]) -> bool {
+        match self {
+            Required::Any => true,
+            Required::All(needed) => needed.iter().all(|t| trigrams.binary_search(t).is_ok()),
+            Required::And(parts) => parts.iter().all(|part| part.is_satisfied_by(trigrams)),
+            Required::Or(parts) => parts.iter().any(|part| part.is_satisfied_by(trigrams)),
+        }
+    }
+}
+
+// at most this many strings are tracked for a part of a regex, [ab][cd][ef] is 8 already
+const MAX_EXACT: usize = 16;
+
+// what part of a regex matches: every string it can match when there are only a few of them,
+// otherwise just what every match needs
+// this is the idea of Russ Cox's "Regular Expression Matching with a Trigram Index", a lot simpler
+enum Literals {
+    Exact(Vec<String>),
+    Inexact(Required),
+}
+
+impl Literals {
+    fn of(hir: &Hir) -> Literals {
+        match hir.kind() {
+            HirKind::Empty | HirKind::Look(_) => Literals::Exact(vec![String::new()]),
+            HirKind::Literal(literal) => {
+                Literals::Exact(vec![String::from_utf8_lossy(&literal.0).into_owned()])
+            }
+            // a small class like [kK] is a few one char strings
+            HirKind::Class(Class::Unicode(class)) => {
+                let chars: Vec<String> = class
+                    .iter()
+                    .flat_map(|range| range.start()..=range.end())
+                    .take(MAX_EXACT + 1)
+                    .map(String::from)
+                    .collect();
+                if chars.len() <= 4 {
+                    Literals::Exact(chars)
+                } else {
+                    Literals::Inexact(Required::Any)
+                }
+            }
+            HirKind::Class(Class::Bytes(_)) => Literals::Inexact(Required::Any),
+            // a+ needs what a needs, a* and a? need nothing
+            HirKind::Repetition(repetition) if repetition.min > 0 => {
+                Literals::Inexact(Literals::of(&repetition.sub).into_required())
+            }
+            HirKind::Repetition(_) => Literals::Inexact(Required::Any),
+            HirKind::Capture(capture) => Literals::of(&capture.sub),
+            HirKind::Concat(parts) => Literals::concat(parts),
+            HirKind::Alternation(parts) => {
+                let parts: Vec<Literals> = parts.iter().map(Literals::of).collect();
+                let all_exact = parts.iter().all(|p| matches!(p, Literals::Exact(_)));
+                let count: usize = parts
+                    .iter()
+                    .map(|p| match p {
+                        Literals::Exact(strings) => strings.len(),
+                        Literals::Inexact(_) => 0,
+                    })
+                    .sum();
+                if all_exact && count <= MAX_EXACT {
+                    let mut strings = Vec::with_capacity(count);
+                    for part in parts {
+                        if let Literals::Exact(part) = part {
+                            strings.extend(part);
+                        }
+                    }
+                    Literals::Exact(strings)
+                } else {
+                    Literals::Inexact(Required::or(
+                        parts.into_iter().map(Literals::into_required).collect(),
+                    ))
+                }
+            }
+        }
+    }
+
+    // the strings of the parts one after the other are joined, as long as there are not too
+    // many of them, then what we have so far becomes one requirement and we start again
+    fn concat(parts: &[Hir]) -> Literals {
+        let mut current = vec![String::new()];
+        let mut required = Vec::new();
+        let mut exact = true;
+        for part in parts {
+            match Literals::of(part) {
+                Literals::Exact(strings) if current.len() * strings.len() <= MAX_EXACT => {
+                    current = current
+                        .iter()
+                        .flat_map(|a| strings.iter().map(move |b| format!("{}{}", a, b)))
+                        .collect();
+                }
+                part => {
+                    exact = false;
+                    let done = std::mem::replace(&mut current, vec![String::new()]);
+                    required.push(Literals::Exact(done).into_required());
+                    required.push(part.into_required());
+                }
+            }
+        }
+        if exact {
+            return Literals::Exact(current);
+        }
+        required.push(Literals::Exact(current).into_required());
+        Literals::Inexact(Required::and(required))
+    }
+
+    fn into_required(self) -> Required {
+        match self {
+            Literals::Exact(strings) => {
+                Required::or(strings.iter().map(|s| Required::literal(s)).collect())
+            }
+            Literals::Inexact(required) => required,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trigrams(text: &str) -> Vec<u32> {
+        match Required::literal(text) {
+            Required::All(trigrams) => trigrams,
+            _ => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn what_patterns_require() {
+        let file = trigrams("fn main() { println!(\"Hello, World\") }");
+        let may_match = |config: Config| Required::of(&config).is_satisfied_by(&file);
+        let config = |patterns: &[&str], regex: bool| Config {
+            patterns: patterns.iter().map(|p| p.to_string()).collect(),
+            regex,
+            ..Config::default()
+        };
+
+        assert!(may_match(config(&["println"], false)));
+        // the index is case folded, it can only rule out what matches in no case at all
+        assert!(may_match(config(&["WORLD"], false)));
+        assert!(!may_match(config(&["eprintln"], false)));
+        assert!(may_match(config(&["eprintln", "main"], false)));
+        assert!(may_match(config(&["ab"], false)));
+        assert!(!may_match(config(&[], false)));
+
+        assert!(may_match(config(&[r"fn \w+\(\)"], true)));
+        assert!(may_match(config(&["(hello|goodbye), world"], true)));
+        assert!(!may_match(config(&["(hi|goodbye), world"], true)));
+        assert!(!may_match(config(&["print(ln|f)!?xyz"], true)));
+        assert!(may_match(config(&["x?print"], true)));
+        assert!(may_match(config(&["[mp]ain[(]"], true)));
+        assert!(!may_match(config(&["[mp]ain[xz]"], true)));
+        assert_eq!(Required::Any, Required::regex(r"\w+"));
+        assert_eq!(Required::Any, Required::regex("("));
+
+        let query = |text: &str| Config {
+            boolean_query: Some(crate::query::Scope::Line),
+            ..config(&[text], false)
+        };
+        assert!(may_match(query("main AND NOT eprintln")));
+        assert!(!may_match(query("main NEAR/1 eprintln")));
+        let config = Config {
+            invert: true,
+            ..config(&["eprintln"], false)
+        };
+        assert!(may_match(config));
+    }
+
+    #[test]
+    fn updates_only_what_changed() {
+        let dir = std::env::temp_dir().join(format!("minigrep_index_{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("a.txt"), "Alpha beta\n").unwrap();
+        fs::write(dir.join("sub/b.txt"), "gamma\n").unwrap();
+        let options = IndexOptions {
+            dir: dir.clone(),
+            ..IndexOptions::default()
+        };
+
+        let summary = update(&options).unwrap();
+        assert_eq!((2, 2, 0, false), summary_numbers(&summary));
+        let index = Index::load(&dir).unwrap();
+        assert_eq!(index, Index::decode(&index.encode()).unwrap());
+        let alpha = Required::literal("ALPHA");
+        assert!(index.may_match(&dir, &dir.join("a.txt"), &alpha));
+        assert!(!index.may_match(&dir, &dir.join("sub/b.txt"), &alpha));
+
+        // a changed file is read again, a deleted one is dropped
+        fs::write(dir.join("sub/b.txt"), "gamma alpha\n").unwrap();
+        fs::remove_file(dir.join("a.txt")).unwrap();
+        let summary = update(&options).unwrap();
+        assert_eq!((1, 1, 1, false), summary_numbers(&summary));
+        let index = Index::load(&dir).unwrap();
+        assert!(index.may_match(&dir, &dir.join("sub/b.txt"), &alpha));
+        assert_eq!(
+            (1, 0, 0, false),
+            summary_numbers(&update(&options).unwrap())
+        );
+
+        // another version (or garbage) is not trusted, the index is built again
+        let mut bytes = fs::read(Index::path(&dir)).unwrap();
+        bytes[MAGIC.len()] = 99;
+        fs::write(Index::path(&dir), &bytes).unwrap();
+        assert!(Index::load(&dir)
+            .unwrap_err()
+            .to_string()
+            .contains("version 99"));
+        assert_eq!((1, 1, 0, true), summary_numbers(&update(&options).unwrap()));
+        fs::write(Index::path(&dir), &bytes[..bytes.len() - 1]).unwrap();
+        assert!(Index::load(&dir).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn numbers_too_big_for_a_u64_are_damage() {
+        let mut index = Index::default();
+        let stamp = Stamp {
+            size: u64::MAX,
+            secs: 1,
+            nanos: 2,
+        };
+        let trigrams = vec![7];
+        index
+            .entries
+            .insert("big".to_string(), Entry { stamp, trigrams });
+        let mut bytes = index.encode();
+        assert_eq!(index, Index::decode(&bytes).unwrap());
+
+        // u64::MAX is nine 0xff and a 0x01, a 0x03 there would need a 65th bit
+        let max = [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01];
+        let at = bytes.windows(10).position(|w| w == max).unwrap();
+        bytes[at + 9] = 0x03;
+        assert!(Index::decode(&bytes)
+            .unwrap_err()
+            .to_string()
+            .contains("damaged"));
+    }
+
+    fn summary_numbers(summary: &Summary) -> (usize, usize, usize, bool) {
+        (
+            summary.files,
+            summary.updated,
+            summary.removed,
+            summary.rebuilt,
+        )
+    }
+}
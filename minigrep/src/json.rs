@@ -0,0 +1,109 @@
+// just enough JSON for --json, we only ever write it so we dont need a whole crate for it
+use std::fmt::Write;
+
+// s as a JSON string, with the quotes around it
+pub fn string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            // every other control char needs a \u escape, like the NUL bytes of a binary file
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// {"text":"..."} when bytes are UTF-8, otherwise {"bytes":"..."} with the bytes in base64
+// JSON strings can only hold text, this way a Latin-1 line still comes out byte for byte
+pub fn data(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => format!("{{\"text\":{}}}", string(text)),
+        Err(_) => format!("{{\"bytes\":\"{}\"}}", base64(bytes)),
+    }
+}
+
+// where byte `at` of String::from_utf8_lossy(raw) comes from in raw, for the offsets of a line
+// that is written as bytes: every bad sequence is one 3 byte U+FFFD in the lossy text
+pub fn raw_offset(raw: &[u8], at: usize) -> usize {
+    let (mut lossy, mut pos) = (0, 0);
+    let mut rest = raw;
+    loop {
+        let (valid, bad) = match std::str::from_utf8(rest) {
+            Ok(_) => (rest.len(), 0),
+            Err(e) => (
+                e.valid_up_to(),
+                e.error_len().unwrap_or(rest.len() - e.valid_up_to()),
+            ),
+        };
+        if at <= lossy + valid || bad == 0 {
+            return pos + at.saturating_sub(lossy).min(valid);
+        }
+        // a span never starts or ends inside the U+FFFD, only before or after it
+        lossy += valid + '\u{fffd}'.len_utf8();
+        pos += valid + bad;
+        if at <= lossy {
+            return pos;
+        }
+        rest = &rest[valid + bad..];
+    }
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// standard base64 with = padding, every 3 bytes become 4 chars
+pub fn base64(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strings_and_bytes() {
+        assert_eq!(
+            r#""say \"hi\"\\n\t\u0000 ü""#,
+            string("say \"hi\"\\n\t\0 ü")
+        );
+        assert_eq!(r#"{"text":"café"}"#, data("café".as_bytes()));
+        assert_eq!(r#"{"bytes":"Y2Fm6Q=="}"#, data(b"caf\xe9"));
+        assert_eq!("", base64(b""));
+        assert_eq!("TWFu", base64(b"Man"));
+        assert_eq!("TWE=", base64(b"Ma"));
+
+        // the lossy text is "caf\u{fffd} frog\u{fffd}\u{fffd}!"
+        let raw = b"caf\xe9 frog\xff\xfe!";
+        assert_eq!(3, raw_offset(raw, 3));
+        assert_eq!(5, raw_offset(raw, 7));
+        assert_eq!(9, raw_offset(raw, 11));
+        assert_eq!(10, raw_offset(raw, 14));
+        assert_eq!(11, raw_offset(raw, 17));
+        assert_eq!(12, raw_offset(raw, 18));
+    }
+}
@@ -1,14 +1,159 @@
 // this is our route of our library crate
-use std::fs; // to read file and to do operations with filesystem
-             // this is our first rust project
+use std::fs::{self, File}; // to read file and to do operations with filesystem
+                           // this is our first rust project
+use std::collections::HashSet;
 use std::env;
-use std::error::Error;
+use std::io::{self, BufRead, BufReader, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Instant;
+
+use regex::{Regex, RegexBuilder}; // external crate for regular expressions (added in Cargo.toml)
+
+pub mod args; // command line parsing and --help
+pub mod encoding; // UTF-16 and Latin-1 files are turned into UTF-8 while reading
+pub mod error;
+pub mod fold; // case insensitive matching with simple case folding
+pub mod fuzzy; // --fuzzy, approximate matching with Myers' bit-parallel algorithm
+pub mod glob; // shell style globs for --glob, --type and ignore files
+pub mod ignore; // .gitignore and .ignore rules
+pub mod index; // minigrep index, a trigram index that tells search --index which files to skip
+pub mod json; // the bits of JSON that --json writes
+pub mod matcher;
+pub mod pool; // worker threads for searching many files at once
+pub mod printer;
+pub mod query; // --query, AND, OR, NOT and NEAR/k between terms
+pub mod replace; // --replace with --in-place and --dry-run
+pub mod search; // streaming search over any BufRead
+pub mod searcher; // the library API, a Searcher builder with lazy iterators
+pub mod walk; // recursive directory search lives in src/walk.rs
+pub mod watch; // --watch, polls the files and searches what changed
+
+use args::Command;
+use encoding::Encoding;
+pub use error::MinigrepError;
+pub use matcher::Matcher;
+use printer::Printer;
+pub use printer::{ColorChoice, OutputMode};
+use query::{QueryMatcher, Scope};
+pub use search::Found;
+use search::{Context, Event, Record};
+pub use searcher::{CaseMode, Searcher, SearcherBuilder};
 
 // now we need to declare our function and struct as public
 
-pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
+// returns true when at least one line was selected, main uses this for grep's exit status:
+// 0 when something matched, 1 when nothing matched and 2 for any MinigrepError
+pub fn run(config: Config) -> Result<bool, MinigrepError> {
     // this is an error type
-    let contents = fs::read_to_string(config.filename)?; // the ? will return error if it not able to read file
+    let started = Instant::now();
+
+    // we build the matcher once here, a directory can contain thousands of files
+    let matcher = Matcher::new(&config)?; // bad regex pattern becomes an error
+
+    // we used to read the whole file with fs::read_to_string and search the String,
+    // now lines are streamed through a BufReader so big log files dont have to fit in memory
+    let stdout = io::stdout();
+    let color = printer::use_color(config.color, stdout.is_terminal(), env::var_os("NO_COLOR"));
+    let mut printer = Printer::new(stdout.lock(), &config).with_color(color);
+    let options = search::Options {
+        context: Context {
+            before: config.before_context,
+            after: config.after_context,
+        },
+        invert: config.invert,
+        // to know if a file matches at all, the first match is enough
+        max_count: match config.output {
+            OutputMode::FilesWithMatches | OutputMode::FilesWithoutMatch | OutputMode::Quiet => {
+                Some(1)
+            }
+            OutputMode::Lines | OutputMode::Count => None,
+        },
+        ..search::Options::default()
+    };
+
+    // every file we have to search, directories are expanded here
+    // --glob and --type only apply to files found inside directories
+    let walk_options = walk::WalkOptions {
+        hidden: config.hidden,
+        ignore: !config.no_ignore,
+        filter: ignore::FileFilter::new(&config.globs, &config.types, &config.types_not)?,
+    };
+    // with --index the files of a directory its index rules out are not read at all
+    let required = if config.index {
+        index::Required::of(&config)
+    } else {
+        index::Required::Any
+    };
+    let mut inputs = Vec::new();
+    let mut failed = 0;
+    let mut show_name = config.filenames.len() > 1;
+    for filename in &config.filenames {
+        let path = Path::new(filename);
+        if filename == "-" {
+            // - means read from standard input, like cat file.txt | minigrep to -
+            inputs.push(Input {
+                name: printer::STDIN_NAME.to_string(),
+                path: None,
+                ruled_out: false,
+            });
+        } else if path.is_dir() {
+            // every match is printed as path:line so we know which file it came from
+            show_name = true;
+            let index = if config.index { load_index(path) } else { None };
+            match walk::walk_with(path, &walk_options) {
+                Ok(walk) => {
+                    // an unreadable directory inside is reported like a missing file
+                    for (dir, e) in walk.errors {
+                        eprintln!("{}", MinigrepError::io(&dir.display().to_string(), e));
+                        failed += 1;
+                    }
+                    inputs.extend(
+                        walk.files
+                            .into_iter()
+                            .filter(|file| !index::is_index_file(file))
+                            .map(|file| Input {
+                                name: file.display().to_string(),
+                                ruled_out: index
+                                    .as_ref()
+                                    .is_some_and(|index| !index.may_match(path, &file, &required)),
+                                path: Some(file),
+                            }),
+                    );
+                }
+                Err(e) if config.filenames.len() == 1 => {
+                    return Err(MinigrepError::io(filename, e))
+                }
+                Err(e) => {
+                    eprintln!("{}", MinigrepError::io(filename, e));
+                    failed += 1;
+                }
+            }
+        } else {
+            inputs.push(Input {
+                name: filename.clone(),
+                path: Some(path.to_path_buf()),
+                ruled_out: false,
+            });
+        }
+    }
+
+    // --in-place and --dry-run rewrite whole files instead of printing lines
+    if config.in_place || config.dry_run {
+        return rewrite_files(&config, &matcher, &inputs, failed);
+    }
+
+    // --watch searches every file once and then keeps going until it is stopped with Ctrl-C
+    if config.watch {
+        return watch_files(
+            &config,
+            &matcher,
+            &options,
+            &mut printer,
+            &walk_options,
+            show_name,
+        );
+    }
 
     // println!("Reading file: {}", contents);
 
@@ -24,27 +169,465 @@ pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
     // Ok(())
 
     // Now we have two function case sensitive and insensitive so our program needs to figure out which to use
-    // we will do this using enviornment variables
+    // we will do this using enviornment variables, the Matcher takes care of that
+    let mut matched = false;
+    let threads = match config.threads {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    };
+    let quiet = config.output == OutputMode::Quiet;
+    // only --json prints these, at the very end
+    let mut stats = printer::Stats::default();
+
+    if threads == 1 || inputs.len() == 1 {
+        // one at a time, straight to stdout so matches show up as soon as they are found
+        for input in &inputs {
+            match search_input(input, &matcher, &options, &mut printer, show_name, &config) {
+                Ok(count) => {
+                    matched |= count > 0;
+                    stats.add(count, printer.matched_lines());
+                }
+                // a single file that could not be read is reported as it is
+                Err(e) if inputs.len() == 1 && config.filenames.len() == 1 => return Err(e),
+                // one unreadable (or binary) file should not stop the whole search
+                Err(e) => {
+                    eprintln!("{}", e);
+                    failed += 1;
+                }
+            }
+            // -q stops at the very first match, there is no need to look at other files
+            if matched && quiet {
+                return Ok(true);
+            }
+        }
+    } else {
+        // every worker prints one file into its own buffer, the buffers are written out in order
+        // so the output is the same as with -j 1, whichever thread finishes first
+        let mut write_error = None;
+        pool::run_ordered(
+            inputs.len(),
+            threads,
+            |i| {
+                let mut buffer = Printer::new(Vec::new(), &config).with_color(color);
+                let result = search_input(
+                    &inputs[i],
+                    &matcher,
+                    &options,
+                    &mut buffer,
+                    show_name,
+                    &config,
+                );
+                let lines = buffer.matched_lines();
+                (buffer.into_inner(), lines, result)
+            },
+            |_, (output, lines, result)| {
+                if let Err(e) = printer.write_chunk(&output) {
+                    write_error = Some(MinigrepError::io("(standard output)", e));
+                    return false;
+                }
+                match result {
+                    Ok(count) => {
+                        matched |= count > 0;
+                        stats.add(count, lines);
+                    }
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        failed += 1;
+                    }
+                }
+                !(matched && quiet)
+            },
+        );
+        if let Some(e) = write_error {
+            return Err(e);
+        }
+    }
+
+    if config.json {
+        printer
+            .summary(&stats, started.elapsed())
+            .map_err(|e| MinigrepError::io("(standard output)", e))?;
+    }
+
+    // -q found something, so errors dont matter
+    if matched && quiet {
+        return Ok(true);
+    }
+    // like grep, a failed file makes the exit status 2 even if other files matched
+    // the errors were already printed in order with the output
+    if failed > 0 {
+        return Err(MinigrepError::Incomplete { failed });
+    }
+
+    Ok(matched)
+}
+
+// applies --replace to every input, prints a diff with --dry-run and writes the files otherwise
+// returns true when any file had a match, the same as a search
+fn rewrite_files(
+    config: &Config,
+    matcher: &Matcher,
+    inputs: &[Input],
+    mut failed: usize,
+) -> Result<bool, MinigrepError> {
+    let replacement = config.replace.as_deref().unwrap_or_default();
+    let mut stdout = io::stdout().lock();
+    let mut matched = false;
+    for input in inputs {
+        let result = rewrite_file(config, matcher, input, replacement, &mut stdout);
+        match result {
+            Ok(changed) => matched |= changed,
+            Err(e) if inputs.len() == 1 && config.filenames.len() == 1 => return Err(e),
+            Err(e) => {
+                eprintln!("{}", e);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(MinigrepError::Incomplete { failed });
+    }
+    Ok(matched)
+}
+
+fn rewrite_file<W: Write>(
+    config: &Config,
+    matcher: &Matcher,
+    input: &Input,
+    replacement: &str,
+    out: &mut W,
+) -> Result<bool, MinigrepError> {
+    let error = |e| MinigrepError::io(&input.name, e);
+    let path = input.path.as_ref().ok_or_else(|| {
+        MinigrepError::BadArgs("--in-place can not write to standard input".to_string())
+    })?;
+    let bytes = fs::read(path).map_err(error)?;
+    // the encoding a search would read it in, the file is written back in it (with its BOM)
+    let (encoding, bom) = encoding::detect(&bytes, config.encoding);
+    let utf16 = matches!(
+        encoding,
+        Encoding::Utf16 | Encoding::Utf16Le | Encoding::Utf16Be
+    );
+    // we only write back what we can read: binary files are left alone
+    // (UTF-16 is full of NUL bytes, that does not make it binary)
+    if !utf16 && !config.text && search::is_binary(&mut bytes.as_slice()).map_err(error)? {
+        // but a match in one must not look like no match at all, say it was skipped and count it
+        let lossy = String::from_utf8_lossy(&bytes);
+        if replace::Rewrite::new(matcher, &lossy, replacement, config.regex).changed() == 0 {
+            return Ok(false);
+        }
+        eprintln!(
+            "Binary file {} matches, it is left as it is (-a rewrites it anyway)",
+            input.name
+        );
+        return Ok(true);
+    }
+    let contents = match encoding::decode_exact(&bytes[bom..], encoding) {
+        Some(contents) => contents,
+        None if encoding == Encoding::Utf8 => {
+            return Err(MinigrepError::InvalidUtf8 {
+                path: input.name.clone(),
+            })
+        }
+        None => {
+            return Err(error(io::Error::other(
+                "not valid UTF-16, it is left as it is",
+            )))
+        }
+    };
+
+    let rewrite = replace::Rewrite::new(matcher, &contents, replacement, config.regex);
+    if rewrite.changed() == 0 {
+        return Ok(false);
+    }
+    if config.dry_run {
+        out.write_all(rewrite.unified_diff(&input.name).as_bytes())
+            .map_err(|e| MinigrepError::io("(standard output)", e))?;
+    } else {
+        let mut new = bytes[..bom].to_vec();
+        new.extend(
+            encoding::encode(&rewrite.contents(), encoding).ok_or_else(|| {
+                error(io::Error::other(
+                    "the replacement has characters Latin-1 can not hold",
+                ))
+            })?,
+        );
+        replace::write_atomic(path, &new, config.backup).map_err(error)?;
+    }
+    Ok(true)
+}
+
+// the index of a directory for --index, without one every file is searched as usual
+fn load_index(dir: &Path) -> Option<index::Index> {
+    match index::Index::load(dir) {
+        Ok(index) => Some(index),
+        Err(e) => {
+            let name = index::Index::path(dir).display().to_string();
+            eprintln!(
+                "{}, searching every file (run minigrep index {} to build it)",
+                MinigrepError::io(&name, e),
+                dir.display()
+            );
+            None
+        }
+    }
+}
 
-    let result = if config.case_sensitive {
-        // in rust we dont put semi-colons in end if we are directly returning a value
-        search_sensitive(&config.query, &contents)
+// the loop behind --watch, it only returns when the output can not be written anymore
+// directories are walked again every time, so new files in them are found too
+fn watch_files<W: Write>(
+    config: &Config,
+    matcher: &Matcher,
+    options: &search::Options,
+    printer: &mut Printer<W>,
+    walk_options: &walk::WalkOptions,
+    show_name: bool,
+) -> Result<bool, MinigrepError> {
+    // the old lines -B needs are searched again with the new ones, a -U match or a
+    // NEAR/k can reach back any number of lines, so those read the whole file
+    let keep = if config.multiline || config.boolean_query.is_some() {
+        None
     } else {
-        search_insensitive(&config.query, &contents)
+        Some(config.before_context)
     };
+    let mut watcher = watch::Watcher::new(keep);
+    // every error is printed once, not every INTERVAL, until the file can be searched again
+    let mut reported = HashSet::new();
+    loop {
+        for path in watched_files(config, walk_options) {
+            let name = path.display().to_string();
+            let result = watcher
+                .check(&path)
+                .and_then(|(change, stamp)| match change {
+                    watch::Change::Unchanged => Ok(0),
+                    // only the new lines are printed, numbered from where the old ones stopped
+                    watch::Change::Appended => {
+                        let Some(chunk) = watcher.appended(&path, stamp)? else {
+                            return Ok(0);
+                        };
+                        let options = search::Options {
+                            lines_before: chunk.lines_before,
+                            bytes_before: chunk.bytes_before,
+                            searched_lines: chunk.searched_lines,
+                            ..*options
+                        };
+                        printer.begin_file(&name, show_name);
+                        let reader = encoding::transcode(chunk.data.as_slice(), chunk.encoding);
+                        search_and_print(matcher, reader, &options, printer, config)
+                    }
+                    watch::Change::New | watch::Change::Rewritten => {
+                        if change == watch::Change::Rewritten {
+                            eprintln!("{}: rewritten, all of its matches again", name);
+                        }
+                        // what is written after we looked at the size is left for the next time
+                        let file = File::open(&path)?;
+                        let mut reader = BufReader::new(file.take(stamp.size));
+                        let (encoding, bom) = encoding::detect(reader.fill_buf()?, config.encoding);
+                        let mut tracked = watcher.track(reader, encoding, bom);
+                        tracked.consume(bom);
+                        printer.begin_file(&name, show_name);
+                        let reader = encoding::transcode(&mut tracked, encoding);
+                        let count = search_and_print(matcher, reader, options, printer, config)?;
+                        watcher.searched_all(&path, stamp, tracked)?;
+                        Ok(count)
+                    }
+                });
+            match result {
+                Ok(_) => {
+                    reported.remove(&path);
+                }
+                // nobody is reading anymore, like minigrep ... | head
+                Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {
+                    return Err(MinigrepError::io("(standard output)", e))
+                }
+                // gone, not there yet or not readable: it is new again when it can be read
+                Err(e) => {
+                    if !reported.contains(&path) {
+                        eprintln!("{}", MinigrepError::io(&name, e));
+                        reported.insert(path.clone());
+                    }
+                    watcher.forget(&path);
+                }
+            }
+        }
+        thread::sleep(watch::INTERVAL);
+    }
+}
 
-    for line in result {
-        println!("{}", line);
+// every file --watch looks at, a directory that can not be read is tried again next time
+fn watched_files(config: &Config, walk_options: &walk::WalkOptions) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for filename in &config.filenames {
+        let path = Path::new(filename);
+        if path.is_dir() {
+            let found = walk::walk_with(path, walk_options)
+                .unwrap_or_default()
+                .files;
+            files.extend(found.into_iter().filter(|file| !index::is_index_file(file)));
+        } else {
+            files.push(path.to_path_buf());
+        }
     }
+    files
+}
 
-    Ok(())
+// one thing to search, a file or stdin when path is None
+struct Input {
+    name: String,
+    path: Option<PathBuf>,
+    // the index knows it has no match, so it is not even opened
+    ruled_out: bool,
+}
+
+fn search_input<W: Write>(
+    input: &Input,
+    matcher: &Matcher,
+    options: &search::Options,
+    printer: &mut Printer<W>,
+    show_name: bool,
+    config: &Config,
+) -> Result<u64, MinigrepError> {
+    printer.begin_file(&input.name, show_name);
+    if input.ruled_out {
+        // -c and -L still have to print it, like any other file without a match
+        return printer
+            .end_file(0)
+            .map(|_| 0)
+            .map_err(|e| MinigrepError::io("(standard output)", e));
+    }
+    let result = match &input.path {
+        None => encoding::decode(io::stdin().lock(), config.encoding)
+            .and_then(|reader| search_and_print(matcher, reader, options, printer, config)),
+        // the ? will return error if it not able to read file
+        Some(path) => File::open(path)
+            .and_then(|file| encoding::decode(BufReader::new(file), config.encoding))
+            .and_then(|reader| search_and_print(matcher, reader, options, printer, config)),
+    };
+    result.map_err(|e| MinigrepError::text(&input.name, e))
+}
+
+// searches one input and prints it, begin_file has to be called before
+fn search_and_print<R: BufRead, W: Write>(
+    matcher: &Matcher,
+    mut reader: R,
+    options: &search::Options,
+    printer: &mut Printer<W>,
+    config: &Config,
+) -> io::Result<u64> {
+    // the lines of a binary file would only mess up the terminal, so like grep we just say
+    // that it matches, the first match is enough to know that
+    if printer.summarizes_binary() && search::is_binary(&mut reader)? {
+        let first = search::Options {
+            context: Context::default(),
+            max_count: Some(1),
+            ..*options
+        };
+        let count = search_with(matcher, reader, &first, config, |_| Ok(()))?;
+        if count > 0 {
+            printer.binary_matches()?;
+        }
+        return Ok(count);
+    }
+    let count = search_with(matcher, reader, options, config, |event| match event {
+        // --replace prints the line as it would look after the replacement
+        Event::Match(record) if config.replace.is_some() && !record.spans.is_empty() => {
+            let replacement = config.replace.as_deref().unwrap_or_default();
+            let (line, spans) = matcher.replace(record.line, replacement, config.regex);
+            printer.event(Event::Match(Record {
+                line: &line,
+                raw: line.as_bytes(),
+                spans,
+                ..record
+            }))
+        }
+        event => printer.event(event),
+    })?;
+    printer.end_file(count)?;
+    Ok(count)
+}
+
+// --multiline and --query need the whole input at once, everything else is streamed line by line
+fn search_with<R, F>(
+    matcher: &Matcher,
+    reader: R,
+    options: &search::Options,
+    config: &Config,
+    on_event: F,
+) -> io::Result<u64>
+where
+    R: BufRead,
+    F: FnMut(Event) -> io::Result<()>,
+{
+    if let Matcher::Query(query) = matcher {
+        search::search_query(query, reader, options, on_event)
+    } else if config.multiline {
+        search::search_multiline(matcher, reader, options, on_event)
+    } else {
+        search::search_reader_with(matcher, reader, options, on_event)
+    }
 }
 
 // create structs to more clear
 pub struct Config {
-    pub query: String,
-    pub filename: String,
+    // what to search for, usually just the QUERY argument but -e and -f can give many
+    // a line matches when any of them matches
+    pub patterns: Vec<String>,
+    // files or directories to search, - is stdin
+    pub filenames: Vec<String>,
     pub case_sensitive: bool,
+    // --smart-case, case_sensitive was already worked out from the patterns when this is set
+    pub smart_case: bool,
+    pub regex: bool,  // treat query as a regular expression instead of a plain string
+    pub hidden: bool, // also search hidden files and folders when searching a directory
+    pub no_ignore: bool, // dont skip what .gitignore and .ignore files list
+    // --glob filters, a ! in front excludes, the last matching one wins
+    pub globs: Vec<String>,
+    // --type and --type-not names like rust or py
+    pub types: Vec<String>,
+    pub types_not: Vec<String>,
+    // number of lines to print before and after every match (-B, -A and -C for both)
+    pub before_context: usize,
+    pub after_context: usize,
+    // extra information printed in front of every line
+    pub line_number: bool,
+    pub byte_offset: bool,
+    pub column: bool,
+    pub vimgrep: bool,
+    // select the lines that dont match (-v)
+    pub invert: bool,
+    // how files are decoded, None means UTF-8 unless a BOM says otherwise (--encoding)
+    pub encoding: Option<Encoding>,
+    // replace every match with this text when printing, $1 and ${name} are capture groups with -E
+    pub replace: Option<String>,
+    // write the replacements back into the files, keeping FILE.bak with backup
+    pub in_place: bool,
+    pub backup: bool,
+    // print a diff of what --in-place would change instead of changing anything
+    pub dry_run: bool,
+    // print the lines of binary files too instead of "Binary file ... matches" (-a)
+    pub text: bool,
+    // a match has to be a whole word (-w) or the whole line (-x)
+    pub word_regexp: bool,
+    pub line_regexp: bool,
+    // a substring at most this many edits away from the query matches too (--fuzzy)
+    pub fuzzy: Option<usize>,
+    // a match can span lines, the whole file is searched at once (--multiline)
+    pub multiline: bool,
+    // the query is AND, OR, NOT and NEAR/k between terms, about single lines or whole files
+    // (--query and --query-scope), None is a plain search
+    pub boolean_query: Option<Scope>,
+    // skip the files of a directory its trigram index says can not match (--index)
+    pub index: bool,
+    // keep running and search again whenever a file changes (--watch)
+    pub watch: bool,
+    // lines, counts, file names or nothing at all
+    pub output: OutputMode,
+    // one JSON object per line for every file, match and context line (--json)
+    pub json: bool,
+    // highlight matches, file names and line numbers
+    pub color: ColorChoice,
+    // how many files are searched at the same time, 0 means one thread per cpu
+    pub threads: usize,
 }
 
 // parse_config function takes a reference to a vector of strings and returns a reference to a Config struct in Result enum to handle errors
@@ -68,26 +651,68 @@ pub struct Config {
 //   with the Config struct. Making this change will make the code more idiomatic.
 
 impl Config {
-    pub fn new(args: &[String]) -> Result<Config, &str> {
-        // Error Handling
-        if args.len() < 3 {
-            return Err("Not enough arguments");
-        }
-        let query = args[1].clone(); // clone() because we dont want to take ownership
-        let filename = args[2].clone();
-
-        // to set CASE_INSENSITIVE variables run command 
+    // the real parsing lives in src/args.rs, see minigrep --help for every option
+    pub fn new(args: &[String]) -> Result<Config, MinigrepError> {
+        // to set CASE_INSENSITIVE variables run command
         // export CASE_INSENSITIVE = true
-        // cargo run to poem.txt     
+        // cargo run to poem.txt
         // to again reset variable
         // unset CASE_INSENSITIVE
         // cargo run to poem.txt
-        let case_sensitive = env::var("CASE_INSENSITIVE").is_err();
-        Ok(Config {
-            query,
-            filename,
-            case_sensitive,
-        })
+        // -i or -s on the command line win over the variable
+        let case_insensitive_env = env::var("CASE_INSENSITIVE").is_ok();
+        // the config file is left to main (args::config_file), the args are all there is here
+        match args::parse_with_env(args, case_insensitive_env)? {
+            Command::Search(config) => Ok(config),
+            // --help and --version dont need a Config, main handles them with args::parse_with_env
+            Command::Help => Err(MinigrepError::BadArgs(args::help())),
+            Command::Index(_) => Err(MinigrepError::BadArgs(
+                "minigrep index does not search, use index::update".to_string(),
+            )),
+            Command::Version => Err(MinigrepError::BadArgs(args::version())),
+        }
+    }
+}
+
+// an empty search, useful as a starting point when filling in only a few fields
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            patterns: Vec::new(),
+            filenames: Vec::new(),
+            case_sensitive: true,
+            smart_case: false,
+            regex: false,
+            hidden: false,
+            no_ignore: false,
+            globs: Vec::new(),
+            types: Vec::new(),
+            types_not: Vec::new(),
+            before_context: 0,
+            after_context: 0,
+            line_number: false,
+            byte_offset: false,
+            column: false,
+            vimgrep: false,
+            invert: false,
+            encoding: None,
+            replace: None,
+            in_place: false,
+            backup: false,
+            dry_run: false,
+            text: false,
+            word_regexp: false,
+            line_regexp: false,
+            fuzzy: None,
+            multiline: false,
+            boolean_query: None,
+            index: false,
+            watch: false,
+            output: OutputMode::Lines,
+            json: false,
+            color: ColorChoice::Auto,
+            threads: 0,
+        }
     }
 }
 
@@ -100,30 +725,109 @@ pub fn search_sensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
 
     // loop through each line
 
-    let mut results = Vec::new();
+    // let mut results = Vec::new();
 
-    for line in contents.lines() {
-        if line.contains(query) {
-            results.push(line);
-        }
-    }
+    // for line in contents.lines() {
+    //     if line.contains(query) {
+    //         results.push(line);
+    //     }
+    // }
 
-    results
+    // results
+
+    // the loop lives in the Searcher now, it hands out the matches one at a time
+    let searcher = Searcher::builder(query)
+        .build()
+        .expect("a plain string is always a valid pattern");
+    matching_lines(&searcher, contents)
 }
 
 //case insensitive search
 pub fn search_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    let query = query.to_lowercase();
-    let mut results = Vec::new();
+    // this used to call line.to_lowercase() for every line, a new String each time,
+    // and it got ß, the Turkish İ and the Greek final ς wrong
+    // now the query is case folded once and the lines are folded char by char while comparing
+    let searcher = Searcher::builder(query)
+        .case(CaseMode::Insensitive)
+        .build()
+        .expect("a plain string is always a valid pattern");
+    matching_lines(&searcher, contents)
+}
 
-    // contains method always except the string slice
-    for line in contents.lines() {
-        if line.to_lowercase().contains(&query) {
-            results.push(line);
-        }
-    }
+// fuzzy search, lines with a substring at most max_distance edits (Levenshtein distance) away
+// from the query, so a misspelled name is found too: see src/fuzzy.rs
+// fails for a query longer than 64 chars
+pub fn search_fuzzy<'a>(
+    query: &str,
+    max_distance: usize,
+    contents: &'a str,
+) -> Result<Vec<&'a str>, MinigrepError> {
+    let searcher = Searcher::builder(query).fuzzy(max_distance).build()?;
+    Ok(matching_lines(&searcher, contents))
+}
+
+// boolean search, like "timeout AND NOT retry" or "unsafe NEAR/5 transmute", see src/query.rs
+// every term is searched the same way as search_sensitive or search_insensitive does it
+// fails with a message pointing at the mistake when the query does not parse
+pub fn search_query<'a>(
+    query: &str,
+    contents: &'a str,
+    case_sensitive: bool,
+) -> Result<Vec<&'a str>, MinigrepError> {
+    let query = query_matcher(query, Scope::Line, case_sensitive)?;
+    let lines: Vec<&str> = contents.lines().collect();
+    let selected = query.select(&lines);
+    Ok(lines
+        .into_iter()
+        .zip(selected)
+        .filter_map(|(line, selected)| selected.then_some(line))
+        .collect())
+}
 
-    results
+// the same query about the whole of contents, "a AND NOT b" is a with no b anywhere
+pub fn query_matches_file(
+    query: &str,
+    contents: &str,
+    case_sensitive: bool,
+) -> Result<bool, MinigrepError> {
+    let query = query_matcher(query, Scope::File, case_sensitive)?;
+    Ok(query.file(&contents.lines().collect::<Vec<_>>()))
+}
+
+fn query_matcher(
+    query: &str,
+    scope: Scope,
+    case_sensitive: bool,
+) -> Result<QueryMatcher, MinigrepError> {
+    let config = Config {
+        case_sensitive,
+        ..Config::default()
+    };
+    QueryMatcher::new(query, scope, &config)
+}
+
+// found.line borrows from contents already, but as a Cow, so we take the same slice as a &str
+fn matching_lines<'a>(searcher: &Searcher, contents: &'a str) -> Vec<&'a str> {
+    searcher
+        .search_str(contents)
+        .map(|found| {
+            let start = found.byte_offset as usize;
+            &contents[start..start + found.line.len()]
+        })
+        .collect()
+}
+
+// build the regex once so we dont compile it again for every line
+// case insensitive regex uses the (?i) flag of the regex crate instead of lowercasing lines
+pub fn build_regex(query: &str, case_sensitive: bool) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(query)
+        .case_insensitive(!case_sensitive)
+        .build()
+}
+
+// regex search, supports character classes, alternation, anchors, repetition and groups
+pub fn search_regex<'a>(re: &Regex, contents: &'a str) -> Vec<&'a str> {
+    contents.lines().filter(|line| re.is_match(line)).collect()
 }
 
 #[cfg(test)]
@@ -167,4 +871,123 @@ Trust me.";
             search_insensitive(query, contents)
         );
     }
+
+    #[test]
+    fn regex_search() {
+        let contents = "\
+fn main() {
+    let x = add(1, 2);
+}
+fn add(a: i32, b: i32) -> i32 {";
+
+        let re = build_regex(r"fn \w+\(", true).unwrap();
+        assert_eq!(
+            vec!["fn main() {", "fn add(a: i32, b: i32) -> i32 {"],
+            search_regex(&re, contents)
+        );
+
+        // anchors and alternation
+        let re = build_regex(r"^(\}|\s+let)", true).unwrap();
+        assert_eq!(
+            vec!["    let x = add(1, 2);", "}"],
+            search_regex(&re, contents)
+        );
+
+        // repetition with a capture group
+        let re = build_regex(r"(\w+: i32(, )?){2}", true).unwrap();
+        assert_eq!(
+            vec!["fn add(a: i32, b: i32) -> i32 {"],
+            search_regex(&re, contents)
+        );
+    }
+
+    #[test]
+    fn fuzzy_search() {
+        let contents = "\
+ticket 1: Jonathan Smith cant log in
+ticket 2: Jonatan Smyth cant log in either
+ticket 3: printer on fire";
+
+        assert_eq!(
+            vec!["ticket 1: Jonathan Smith cant log in"],
+            search_fuzzy("Jonathan Smith", 0, contents).unwrap()
+        );
+        // a missing h and a y instead of an i are two edits
+        assert_eq!(
+            vec![
+                "ticket 1: Jonathan Smith cant log in",
+                "ticket 2: Jonatan Smyth cant log in either"
+            ],
+            search_fuzzy("Jonathan Smith", 2, contents).unwrap()
+        );
+        assert!(search_fuzzy(&"x".repeat(65), 1, contents).is_err());
+    }
+
+    #[test]
+    fn boolean_query() {
+        let contents = "\
+connect: timeout after 3s
+connect: timeout, retry 1
+connect: ok
+Retry limit reached";
+
+        assert_eq!(
+            vec!["connect: timeout after 3s"],
+            search_query("timeout AND NOT retry", contents, true).unwrap()
+        );
+        assert_eq!(
+            vec!["connect: timeout after 3s", "connect: ok"],
+            search_query("connect NOT retry", contents, false).unwrap()
+        );
+        assert!(query_matches_file("ok NEAR/1 retry", contents, true).unwrap());
+        assert!(!query_matches_file("ok AND NOT timeout", contents, true).unwrap());
+        assert!(search_query("timeout AND", contents, true).is_err());
+    }
+
+    #[test]
+    fn regex_case_insensitive() {
+        let contents = "\
+Rust:
+Trust me.";
+
+        let re = build_regex("^rUsT", false).unwrap();
+        assert_eq!(vec!["Rust:"], search_regex(&re, contents));
+        assert!(build_regex("(unclosed", true).is_err());
+    }
+
+    #[test]
+    fn regex_flag_is_optional() {
+        let args: Vec<String> = ["minigrep", "to", "poem.txt"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert!(!Config::new(&args).unwrap().regex);
+
+        let args: Vec<String> = ["minigrep", "-E", "t[oa]", "poem.txt"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let config = Config::new(&args).unwrap();
+        assert!(config.regex);
+        assert_eq!(vec!["t[oa]"], config.patterns);
+        assert_eq!(vec!["poem.txt"], config.filenames);
+    }
+
+    #[test]
+    fn context_flags() {
+        let args: Vec<String> = ["minigrep", "-C", "2", "to", "-A", "1", "poem.txt"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let config = Config::new(&args).unwrap();
+        assert_eq!(2, config.before_context);
+        assert_eq!(1, config.after_context);
+        assert_eq!(vec!["to"], config.patterns);
+
+        let args: Vec<String> = ["minigrep", "to", "poem.txt", "-B"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert!(Config::new(&args).is_err());
+    }
 }
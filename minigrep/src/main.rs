@@ -1,7 +1,7 @@
 use std::env;
 use std::process; // help us to exit from progrma without panic
 
-use minigrep::Config;    // minigrep is the name of our library crate
+use minigrep::args::{self, Command}; // minigrep is the name of our library crate
 
 // use std::{env, fs, process};   we can also write like this
 
@@ -26,7 +26,7 @@ fn main() {
     let args: Vec<String> = env::args().collect();
     // args() function returns a list of command line arguments and collect()  function converts them into collection of lists
 
-    eprintln!("{:?}", args);
+    // eprintln!("{:?}", args);
 
     // pass arguments like this cargo run sid sid.txt
     // ouptut will be ["target/debug/minigrep", "sid", "sid.txt"]
@@ -38,16 +38,45 @@ fn main() {
 
     // call function directly
 
-    let config = Config::new(&args).unwrap_or_else(|err| {
-        // this is a closure
-        eprintln!("Problem passing arguments:{err}");   // to dont get error in standard output file we use eprintln! macro
-        process::exit(1);
-    });
+    // same as Config::new but --help and --version are handled here instead of being an error
+    let case_insensitive_env = env::var("CASE_INSENSITIVE").is_ok();
+    // options from the config file, like --smart-case, come before the real ones
+    let command = args::with_config_file(&args, args::config_file().as_ref())
+        .and_then(|args| args::parse_with_env(&args, case_insensitive_env))
+        .unwrap_or_else(|err| {
+            // this is a closure
+            eprintln!("Problem passing arguments: {err}"); // to dont get error in standard output file we use eprintln! macro
+            process::exit(2); // 2 means something went wrong, see the exit codes below
+        });
 
     // unwarp_or_else will return the value store in Ok if this is a Ok case. in error case it will execute this closure and exit from program
 
-    println!("Searching for: {}", config.query);
-    println!("In file: {}", config.filename);
+    let config = match command {
+        Command::Search(config) => config,
+        Command::Help => {
+            print!("{}", args::help());
+            return;
+        }
+        Command::Version => {
+            println!("{}", args::version());
+            return;
+        }
+        // minigrep index DIR builds the index that search --index uses, see src/index.rs
+        Command::Index(options) => match minigrep::index::update(&options) {
+            Ok(summary) => {
+                println!("{}", summary);
+                return;
+            }
+            Err(e) => {
+                eprintln!("Application Error: {}", e);
+                process::exit(2);
+            }
+        },
+    };
+
+    // these used to be printed before the results, but they get mixed up with the matches
+    // println!("Searching for: {}", config.query);
+    // println!("In file: {}", config.filename);
 
     // now create a new file in the directory poem.txt to read data from this
     // use fs module for this
@@ -59,9 +88,15 @@ fn main() {
     // println!("Reading file: {}", contents);
 
     //  cargo run sid poem.txt
-    if let Err(e) = minigrep::run(config) {
-        eprintln!("Application Error: {}", e);
-        process::exit(1);
+    // grep's exit codes: 0 when a line was selected, 1 when nothing matched, 2 on any error
+    // scripts use this to tell "no matches" apart from "file missing"
+    match minigrep::run(config) {
+        Ok(true) => process::exit(0),
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            eprintln!("Application Error: {}", e);
+            process::exit(2);
+        }
     }
 
     // run this command to get output in output.txt file
@@ -0,0 +1,327 @@
+// a Matcher decides if a single line matches the query
+// we build it once from the Config and then use it for every line of every file
+use std::ops::Range;
+
+use aho_corasick::{AhoCorasick, MatchKind};
+use regex::Regex;
+
+use crate::fuzzy::Fuzzy;
+use crate::query::QueryMatcher;
+use crate::{build_regex, fold, Config, MinigrepError};
+
+pub enum Matcher {
+    Sensitive(String),
+    // the query is stored already case folded, see src/fold.rs
+    Insensitive(String),
+    Regex(Regex),
+    // many fixed strings at once (-e and -f), Aho-Corasick finds all of them in one pass over the line
+    // when case_sensitive is false the patterns were case folded before building the automaton
+    Multi {
+        automaton: AhoCorasick,
+        case_sensitive: bool,
+    },
+    // a substring a few edits away from the query matches too (--fuzzy)
+    Fuzzy(Fuzzy),
+    // AND, OR, NOT and NEAR/k over many terms (--query), NEAR needs the lines around a line
+    // so search::search_query decides which lines are selected, find_spans is only for
+    // highlighting then: a line of NOT retry is selected without any span
+    Query(QueryMatcher),
+}
+
+impl Matcher {
+    pub fn new(config: &Config) -> Result<Matcher, MinigrepError> {
+        let patterns = &config.patterns;
+
+        // args makes sure there is exactly one plain pattern with --fuzzy
+        if let (Some(max_distance), [query]) = (config.fuzzy, patterns.as_slice()) {
+            return Ok(Matcher::Fuzzy(Fuzzy::new(
+                query,
+                max_distance,
+                config.case_sensitive,
+            )?));
+        }
+
+        // args makes sure the query is the only pattern, its terms get a Matcher each
+        if let (Some(scope), [text]) = (config.boolean_query, patterns.as_slice()) {
+            return Ok(Matcher::Query(QueryMatcher::new(text, scope, config)?));
+        }
+
+        // regex mode is a separate flag, fixed string search is still the default
+        // -w and -x are easiest to do with a regex around the patterns, so with them
+        // fixed strings are escaped and go this way too
+        if config.regex || config.word_regexp || config.line_regexp {
+            let patterns: Vec<String> = if config.regex {
+                patterns.clone()
+            } else {
+                patterns.iter().map(|p| regex::escape(p)).collect()
+            };
+            // one alternation is still a single pass, the regex crate optimizes the literals in it
+            let joined = match patterns.as_slice() {
+                // no patterns at all (an empty -f file) matches nothing, like grep
+                [] => "[^\\s\\S]".to_string(),
+                [single] => single.clone(),
+                _ => patterns
+                    .iter()
+                    .map(|p| format!("(?:{})", p))
+                    .collect::<Vec<_>>()
+                    .join("|"),
+            };
+            let joined = if config.line_regexp {
+                // -x wins over -w, a whole line is a whole word too
+                format!("^(?:{})$", joined)
+            } else if config.word_regexp {
+                // the half boundaries only look outside the match: the char before it and the one
+                // after it must not be word chars (Unicode letters, digits and _), like grep -w
+                format!(r"\b{{start-half}}(?:{})\b{{end-half}}", joined)
+            } else {
+                joined
+            };
+            // with --multiline the regex sees the whole file, ^ and $ should still be about lines
+            let joined = if config.multiline {
+                format!("(?m){}", joined)
+            } else {
+                joined
+            };
+            return Ok(Matcher::Regex(build_regex(&joined, config.case_sensitive)?));
+        }
+
+        // an empty pattern matches every line, the other patterns dont matter then
+        if patterns.len() == 1 || patterns.iter().any(|p| p.is_empty()) {
+            let single = patterns
+                .iter()
+                .find(|p| p.is_empty())
+                .unwrap_or(&patterns[0]);
+            return Ok(if config.case_sensitive {
+                Matcher::Sensitive(single.clone())
+            } else {
+                Matcher::Insensitive(fold::fold(single))
+            });
+        }
+
+        let folded: Vec<String>;
+        let needles = if config.case_sensitive {
+            patterns
+        } else {
+            folded = patterns.iter().map(|p| fold::fold(p)).collect();
+            &folded
+        };
+        // leftmost longest, so "foo" and "foobar" both in the list highlight all of "foobar"
+        // ASCII lines can then be searched as they are, only other lines have to be folded first
+        let automaton = AhoCorasick::builder()
+            .match_kind(MatchKind::LeftmostLongest)
+            .ascii_case_insensitive(!config.case_sensitive)
+            .build(needles)
+            .map_err(|e| MinigrepError::InvalidPattern(e.to_string()))?;
+        Ok(Matcher::Multi {
+            automaton,
+            case_sensitive: config.case_sensitive,
+        })
+    }
+
+    // same checks as search_sensitive, search_insensitive and search_regex but for one line
+    pub fn is_match(&self, line: &str) -> bool {
+        match self {
+            Matcher::Sensitive(query) => line.contains(query.as_str()),
+            Matcher::Insensitive(query) => fold::contains(line, query),
+            Matcher::Regex(re) => re.is_match(line),
+            Matcher::Multi {
+                automaton,
+                case_sensitive: true,
+            } => automaton.is_match(line),
+            Matcher::Multi { automaton, .. } if line.is_ascii() => automaton.is_match(line),
+            Matcher::Multi { automaton, .. } => automaton.is_match(&fold::fold(line)),
+            Matcher::Fuzzy(fuzzy) => fuzzy.is_match(line),
+            Matcher::Query(query) => query.is_match(line),
+        }
+    }
+
+    // byte ranges of every match inside the line, an empty Vec means the line does not match
+    pub fn find_spans(&self, line: &str) -> Vec<Range<usize>> {
+        match self {
+            // an empty query matches every line, but only once
+            Matcher::Sensitive(query) | Matcher::Insensitive(query) if query.is_empty() => {
+                std::iter::once(0..0).collect()
+            }
+            Matcher::Sensitive(query) => line
+                .match_indices(query.as_str())
+                .map(|(start, m)| start..start + m.len())
+                .collect(),
+            Matcher::Insensitive(query) => fold::find_spans(line, query),
+            Matcher::Regex(re) => re.find_iter(line).map(|m| m.range()).collect(),
+            Matcher::Multi {
+                automaton,
+                case_sensitive: true,
+            } => automaton.find_iter(line).map(|m| m.range()).collect(),
+            Matcher::Multi { automaton, .. } if line.is_ascii() => {
+                automaton.find_iter(line).map(|m| m.range()).collect()
+            }
+            Matcher::Multi { automaton, .. } => find_folded(line, |folded| {
+                automaton.find_iter(folded).map(|m| m.range()).collect()
+            }),
+            Matcher::Fuzzy(fuzzy) => fuzzy.find(line).into_iter().map(|m| m.span).collect(),
+            Matcher::Query(query) => query.find_spans(line),
+        }
+    }
+
+    // the line with every match replaced, and where the replacements ended up in the new line
+    // with expand, $1 or ${name} in replacement are capture groups of the regex (--replace with -E)
+    pub fn replace(
+        &self,
+        line: &str,
+        replacement: &str,
+        expand: bool,
+    ) -> (String, Vec<Range<usize>>) {
+        let mut replaced = String::with_capacity(line.len());
+        let mut spans = Vec::new();
+        let mut last = 0;
+        match self {
+            Matcher::Regex(re) if expand => {
+                for caps in re.captures_iter(line) {
+                    let found = caps.get(0).map_or(0..0, |m| m.range());
+                    replaced.push_str(&line[last..found.start]);
+                    let start = replaced.len();
+                    caps.expand(replacement, &mut replaced);
+                    spans.push(start..replaced.len());
+                    last = found.end;
+                }
+            }
+            _ => {
+                for found in self.find_spans(line) {
+                    replaced.push_str(&line[last..found.start]);
+                    spans.push(replaced.len()..replaced.len() + replacement.len());
+                    replaced.push_str(replacement);
+                    last = found.end;
+                }
+            }
+        }
+        replaced.push_str(&line[last..]);
+        (replaced, spans)
+    }
+}
+
+// folding can change the length of a line in bytes (the Kelvin sign is 3 bytes, k only 1), so we
+// remember for every byte of the folded line where its char started in the original line
+// find gets the folded line and returns ranges into it, we turn them back into ranges of line
+fn find_folded<F>(line: &str, find: F) -> Vec<Range<usize>>
+where
+    F: FnOnce(&str) -> Vec<Range<usize>>,
+{
+    let mut folded = String::with_capacity(line.len());
+    let mut starts = Vec::with_capacity(line.len());
+    for (i, c) in line.char_indices() {
+        folded.push(fold::fold_char(c));
+        starts.resize(folded.len(), i);
+    }
+
+    find(&folded)
+        .into_iter()
+        .map(|span| {
+            if span.is_empty() {
+                let start = starts.get(span.start).copied().unwrap_or(line.len());
+                return start..start;
+            }
+            // the match ends at the end of the original char of its last byte
+            let last = starts[span.end - 1];
+            let end = last + line[last..].chars().next().map_or(0, char::len_utf8);
+            starts[span.start]..end
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spans_point_into_the_original_line() {
+        let line = "İstanbul and ISTANBUL";
+        let matcher = Matcher::Insensitive("stanbul".to_string());
+        let spans = matcher.find_spans(line);
+        assert_eq!(vec![2..9, 15..22], spans);
+        assert_eq!("STANBUL", &line[spans[1].clone()]);
+
+        let matcher = Matcher::Sensitive("an".to_string());
+        assert_eq!(vec![4..6, 10..12], matcher.find_spans(line));
+
+        let matcher = Matcher::Regex(Regex::new("[A-Z]+").unwrap());
+        assert_eq!(vec![14..22], matcher.find_spans(line));
+        assert!(matcher.find_spans("no caps").is_empty());
+    }
+
+    fn matcher(patterns: &[&str], case_sensitive: bool, regex: bool) -> Matcher {
+        let config = Config {
+            patterns: patterns.iter().map(|p| p.to_string()).collect(),
+            case_sensitive,
+            regex,
+            ..Config::default()
+        };
+        Matcher::new(&config).unwrap()
+    }
+
+    #[test]
+    fn many_patterns_in_one_pass() {
+        let line = "let foobar = Foo::new(bar);";
+
+        let m = matcher(&["foo", "foobar", "bar"], true, false);
+        assert!(matches!(m, Matcher::Multi { .. }));
+        // leftmost longest: foobar wins over foo at the same position
+        assert_eq!(vec![4..10, 22..25], m.find_spans(line));
+
+        let m = matcher(&["foo", "NEW"], false, false);
+        assert_eq!(vec![4..7, 13..16, 18..21], m.find_spans(line));
+        assert!(!m.is_match("nothing here"));
+
+        let m = matcher(&[r"Foo::\w+", r"\(\w+\)"], true, true);
+        assert_eq!(vec![13..21, 21..26], m.find_spans(line));
+
+        // an empty pattern matches every line, no patterns match nothing
+        assert!(matcher(&["zzz", ""], true, false).is_match("abc"));
+        assert!(!matcher(&[], true, false).is_match("abc"));
+        assert!(!matcher(&[], true, true).is_match("abc"));
+    }
+
+    #[test]
+    fn whole_words_and_lines() {
+        let config = |patterns: &[&str], case_sensitive: bool, word: bool, line: bool| Config {
+            patterns: patterns.iter().map(|p| p.to_string()).collect(),
+            case_sensitive,
+            word_regexp: word,
+            line_regexp: line,
+            ..Config::default()
+        };
+        let words = Matcher::new(&config(&["add"], true, true, false)).unwrap();
+        assert!(!words.is_match("add_two(address, padding)"));
+        assert_eq!(vec![4..7, 10..13], words.find_spans("let add = add(1);"));
+        // Unicode letters are word chars too
+        assert!(!words.is_match("addé"));
+        assert!(words.is_match("é add-on"));
+
+        let words = Matcher::new(&config(&["straße", "ADD"], false, true, false)).unwrap();
+        assert_eq!(vec![0..8, 9..12], words.find_spans("STRAẞE Add add1"));
+        // a pattern with regex chars in it is still a fixed string
+        let words = Matcher::new(&config(&["a+b"], true, true, false)).unwrap();
+        assert_eq!(vec![2..5], words.find_spans("(-a+b)"));
+
+        let line = Matcher::new(&config(&["to an admiring bog!"], false, true, true)).unwrap();
+        assert!(line.is_match("To an admiring bog!"));
+        assert!(!line.is_match("To an admiring bog!!"));
+    }
+
+    #[test]
+    fn replace_with_and_without_groups() {
+        let line = "fn add(a: i32) -> i32 { add_two(a) }";
+        let m = Matcher::Sensitive("add".to_string());
+        let (replaced, spans) = m.replace(line, "sum", false);
+        assert_eq!("fn sum(a: i32) -> i32 { sum_two(a) }", replaced);
+        assert_eq!(vec![3..6, 24..27], spans);
+
+        let m = Matcher::Regex(Regex::new(r"(\w+): (?<ty>i32)").unwrap());
+        let (replaced, _) = m.replace(line, "$ty $1", true);
+        assert_eq!("fn add(i32 a) -> i32 { add_two(a) }", replaced);
+        // a fixed string replacement keeps its $ signs
+        assert_eq!(
+            "fn add($1) -> i32 { add_two(a) }",
+            m.replace(line, "$1", false).0
+        );
+    }
+}
@@ -0,0 +1,192 @@
+// a small worker pool that runs jobs on many threads but hands the results back in order
+// this is how we search many files at once and still print them in a stable order
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Condvar, Mutex};
+use std::thread;
+
+// how many results per thread may wait for an earlier one before the workers stop and wait too
+// without a limit one slow file at the front keeps every later result in memory
+const AHEAD: usize = 4;
+
+// runs job(0), job(1), ... job(count - 1) on `threads` threads
+// done is called on the calling thread with every result in index order, no matter which job finished first
+// when done returns false no new jobs are started (jobs already running still finish but are not reported)
+// a worker does not start a job more than threads * AHEAD past the last result handed out
+pub fn run_ordered<T, J, D>(count: usize, threads: usize, job: J, mut done: D)
+where
+    T: Send,
+    J: Fn(usize) -> T + Sync,
+    D: FnMut(usize, T) -> bool,
+{
+    let next = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+    let (tx, rx) = mpsc::channel();
+    let threads = threads.clamp(1, count.max(1));
+    let window = threads * AHEAD;
+    // how many results were handed out, the workers wait on `moved` for it to go up
+    let handed = Mutex::new(0);
+    let moved = Condvar::new();
+
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            let tx = tx.clone();
+            let (next, stop, job) = (&next, &stop, &job);
+            let (handed, moved) = (&handed, &moved);
+            scope.spawn(move || loop {
+                // every worker takes the next job nobody took yet
+                let i = next.fetch_add(1, Ordering::SeqCst);
+                if i >= count || stop.load(Ordering::SeqCst) {
+                    break;
+                }
+                // the job everyone waits for is always inside the window, so this can not get stuck
+                let mut out = handed.lock().unwrap();
+                while i >= *out + window && !stop.load(Ordering::SeqCst) {
+                    out = moved.wait(out).unwrap();
+                }
+                drop(out);
+                if stop.load(Ordering::SeqCst) {
+                    break;
+                }
+                // a panicking job never sends its result, without this the others would wait for it forever
+                let guard = StopOnPanic {
+                    stop,
+                    handed,
+                    moved,
+                };
+                let result = job(i);
+                std::mem::forget(guard);
+                if tx.send((i, result)).is_err() {
+                    break; // the receiving side is gone
+                }
+            });
+        }
+        // only the workers hold senders now, so the loop below ends when all of them are done
+        drop(tx);
+
+        // results that arrived too early wait here until all results before them were handed out
+        let mut waiting = BTreeMap::new();
+        let mut want = 0;
+        for (i, result) in rx {
+            waiting.insert(i, result);
+            while let Some(result) = waiting.remove(&want) {
+                if !done(want, result) {
+                    stop.store(true, Ordering::SeqCst);
+                    // taking the lock first so no worker misses the wake up between its check and its wait
+                    let _out = handed.lock().unwrap();
+                    moved.notify_all();
+                    return;
+                }
+                want += 1;
+            }
+            *handed.lock().unwrap() = want;
+            moved.notify_all();
+        }
+    });
+}
+
+// stops the pool and wakes every waiting worker when it is dropped, which only happens if the job panicked
+// then all workers end, the receiving loop ends and thread::scope passes the panic on
+struct StopOnPanic<'a> {
+    stop: &'a AtomicBool,
+    handed: &'a Mutex<usize>,
+    moved: &'a Condvar,
+}
+
+impl Drop for StopOnPanic<'_> {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        let _out = self.handed.lock();
+        self.moved.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn results_come_back_in_order() {
+        let mut seen = Vec::new();
+        // the first jobs take the longest, so they finish last
+        run_ordered(
+            8,
+            4,
+            |i| {
+                thread::sleep(Duration::from_millis(40 - 5 * i as u64));
+                i * 10
+            },
+            |i, result| {
+                seen.push((i, result));
+                true
+            },
+        );
+        assert_eq!((0..8).map(|i| (i, i * 10)).collect::<Vec<_>>(), seen);
+    }
+
+    #[test]
+    fn stops_early() {
+        let started = AtomicUsize::new(0);
+        let mut seen = 0;
+        run_ordered(
+            1000,
+            2,
+            |_| {
+                thread::sleep(Duration::from_millis(1));
+                started.fetch_add(1, Ordering::SeqCst)
+            },
+            |_, _| {
+                seen += 1;
+                seen < 3
+            },
+        );
+        assert_eq!(3, seen);
+        assert!(started.load(Ordering::SeqCst) < 1000);
+    }
+
+    #[test]
+    fn slow_first_job_holds_back_the_rest() {
+        let started = AtomicUsize::new(0);
+        let mut started_before_first = 0;
+        run_ordered(
+            200,
+            2,
+            |i| {
+                started.fetch_add(1, Ordering::SeqCst);
+                if i == 0 {
+                    thread::sleep(Duration::from_millis(100));
+                }
+                i
+            },
+            |i, _| {
+                if i == 0 {
+                    started_before_first = started.load(Ordering::SeqCst);
+                }
+                true
+            },
+        );
+        // without the window the other worker would run through all 199 jobs while job 0 sleeps
+        assert!(started_before_first <= 2 * AHEAD, "{started_before_first}");
+        assert_eq!(200, started.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn a_panicking_job_does_not_hang_the_pool() {
+        let panicked = std::panic::catch_unwind(|| {
+            run_ordered(
+                100,
+                2,
+                |i| {
+                    if i == 0 {
+                        thread::sleep(Duration::from_millis(20));
+                        panic!("job 0 failed");
+                    }
+                    i
+                },
+                |_, _| true,
+            )
+        });
+        assert!(panicked.is_err());
+    }
+}
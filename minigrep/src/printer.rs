@@ -0,0 +1,661 @@
+// turns the events of a search into output lines
+// like grep, matching lines use : after every prefix and context lines use -
+use std::ffi::OsString;
+use std::fmt::Display;
+use std::io::{self, Write};
+use std::ops::Range;
+use std::time::Duration;
+
+use crate::fuzzy::Fuzzy;
+use crate::json;
+use crate::search::{Event, Record};
+use crate::Config;
+
+// the name grep uses when it has to print the name of stdin
+pub const STDIN_NAME: &str = "(standard input)";
+
+// what gets printed for every file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputMode {
+    // the matching lines themselves, the default
+    Lines,
+    // only the number of selected lines, -c
+    Count,
+    // only the names of files that have a match, -l
+    FilesWithMatches,
+    // only the names of files without any match, -L
+    FilesWithoutMatch,
+    // nothing at all, -q
+    Quiet,
+}
+
+// --color=auto|always|never
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+// auto means color only when we write to a terminal and the user did not set NO_COLOR
+// (see no-color.org), an explicit --color=always still wins over NO_COLOR
+pub fn use_color(choice: ColorChoice, is_terminal: bool, no_color: Option<OsString>) -> bool {
+    match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => is_terminal && no_color.is_none_or(|value| value.is_empty()),
+    }
+}
+
+// ANSI escape codes, the same colors grep uses
+const MATCH: &str = "\x1b[1;31m"; // bold red
+const NAME: &str = "\x1b[35m"; // magenta
+const NUMBER: &str = "\x1b[32m"; // green
+const SEPARATOR: &str = "\x1b[36m"; // cyan
+const RESET: &str = "\x1b[0m";
+
+// what the --json summary counts, run adds up the files as they are searched
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Stats {
+    pub searches: u64,
+    pub searches_with_match: u64,
+    pub matched_lines: u64,
+}
+
+impl Stats {
+    // count is the number of matches of one file, lines how many lines they are on
+    // (a --multiline match is one match on a few lines, see Printer::matched_lines)
+    pub fn add(&mut self, count: u64, lines: u64) {
+        self.searches += 1;
+        self.searches_with_match += (count > 0) as u64;
+        self.matched_lines += lines;
+    }
+}
+
+pub struct Printer<W: Write> {
+    out: W,
+    mode: OutputMode,
+    line_number: bool,
+    byte_offset: bool,
+    column: bool,
+    // file:line:col:text for every match, this is what vim's :grep understands
+    vimgrep: bool,
+    context: bool,
+    // -a, binary files are printed like text files
+    text: bool,
+    // --json, one JSON object per line instead of grep's output
+    json: bool,
+    // with --fuzzy every match is followed by how far it is from the query
+    fuzzy: Option<Fuzzy>,
+    // in json mode the begin of a file is only written once it has something to show,
+    // the number of submatches is counted for its end
+    began: bool,
+    matches: u64,
+    // every line of the file a match is on, a --multiline match counts all of its lines
+    matched_lines: u64,
+    // set once something was printed, so the next file starts with a -- separator in context mode
+    printed: bool,
+    first_in_file: bool,
+    // the file we are printing right now, and if its name goes in front of every line
+    name: String,
+    show_name: bool,
+    color: bool,
+}
+
+impl<W: Write> Printer<W> {
+    pub fn new(out: W, config: &Config) -> Printer<W> {
+        Printer {
+            out,
+            mode: config.output,
+            line_number: config.line_number,
+            byte_offset: config.byte_offset,
+            column: config.column,
+            vimgrep: config.vimgrep,
+            context: config.before_context > 0 || config.after_context > 0,
+            text: config.text,
+            json: config.json,
+            // a replaced line does not have the matches in it anymore
+            fuzzy: match (config.fuzzy, config.patterns.as_slice(), &config.replace) {
+                (Some(max_distance), [query], None) => {
+                    Fuzzy::new(query, max_distance, config.case_sensitive).ok()
+                }
+                _ => None,
+            },
+            began: false,
+            matches: 0,
+            matched_lines: 0,
+            printed: false,
+            first_in_file: true,
+            name: String::new(),
+            show_name: false,
+            color: false,
+        }
+    }
+
+    // colors are off unless turned on, Printer does not know if out is a terminal
+    pub fn with_color(mut self, color: bool) -> Printer<W> {
+        self.color = color;
+        self
+    }
+
+    // call this before the events of every new file
+    // show_name is set when searching a directory, with a single file we only print the lines
+    pub fn begin_file(&mut self, name: &str, show_name: bool) {
+        self.first_in_file = true;
+        self.name.clear();
+        self.name.push_str(name);
+        self.show_name = show_name;
+        self.began = false;
+        self.matches = 0;
+        self.matched_lines = 0;
+    }
+
+    // how many lines the matches of the file so far are on, for Stats
+    pub fn matched_lines(&self) -> u64 {
+        self.matched_lines
+    }
+
+    pub fn event(&mut self, event: Event) -> io::Result<()> {
+        if let Event::Match(record) = &event {
+            self.matched_lines += record.last_line_number - record.line_number + 1;
+        }
+        if self.json {
+            return self.json_event(event);
+        }
+        if self.mode != OutputMode::Lines {
+            return Ok(()); // the other modes only print something in end_file
+        }
+        if self.vimgrep {
+            // context does not make sense in vimgrep mode, every match becomes its own line
+            return match event {
+                Event::Match(record) => self.vimgrep_line(&record),
+                _ => Ok(()),
+            };
+        }
+
+        // groups from different files are separated too
+        if self.first_in_file && self.context && self.printed && event != Event::Break {
+            self.separator("--")?;
+            writeln!(self.out)?;
+        }
+        self.first_in_file = false;
+        self.printed = true;
+
+        match event {
+            Event::Match(record) => self.line(&record, ":"),
+            Event::Context(record) => self.line(&record, "-"),
+            Event::Break => {
+                self.separator("--")?;
+                writeln!(self.out)
+            }
+        }
+    }
+
+    // writes output another Printer made for one file (see run with more than one thread)
+    // the -- between files in context mode is added here, because only we know what came before
+    pub fn write_chunk(&mut self, chunk: &[u8]) -> io::Result<()> {
+        if chunk.is_empty() {
+            return Ok(());
+        }
+        let grouped = self.mode == OutputMode::Lines && !self.vimgrep && !self.json;
+        if grouped && self.context && self.printed {
+            self.separator("--")?;
+            writeln!(self.out)?;
+        }
+        self.printed = true;
+        self.out.write_all(chunk)
+    }
+
+    // with -c, -l, -L and -q a binary file is searched like any other, they dont print lines
+    // --json has its own way to show bytes that are not text
+    pub fn summarizes_binary(&self) -> bool {
+        self.mode == OutputMode::Lines && !self.text && !self.json
+    }
+
+    // what is printed instead of the lines of a binary file that matches
+    pub fn binary_matches(&mut self) -> io::Result<()> {
+        self.printed = true;
+        writeln!(self.out, "Binary file {} matches", self.name)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    // call this after the events of a file with the number of selected lines (or matches with -U)
+    pub fn end_file(&mut self, count: u64) -> io::Result<()> {
+        if self.json {
+            if !self.began {
+                return Ok(()); // files without anything to show are left out completely
+            }
+            return writeln!(
+                self.out,
+                "{{\"type\":\"end\",\"data\":{{\"path\":{},\"stats\":{{\"matched_lines\":{},\"matches\":{}}}}}}}",
+                json::data(self.name.as_bytes()),
+                self.matched_lines,
+                self.matches
+            );
+        }
+        match self.mode {
+            OutputMode::Count => {
+                if self.show_name {
+                    self.name()?;
+                    self.separator(":")?;
+                }
+                writeln!(self.out, "{}", count)
+            }
+            // the name is printed even for a single file, that is the whole point of -l
+            OutputMode::FilesWithMatches if count > 0 => {
+                self.name()?;
+                writeln!(self.out)
+            }
+            OutputMode::FilesWithoutMatch if count == 0 => {
+                self.name()?;
+                writeln!(self.out)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // the same records grep's output is made of, as {"type":"match","data":{...}}
+    // start and end are byte offsets into "lines", for a line that is not UTF-8 that is the
+    // base64 bytes and not record.line (which has a � for every run of bad bytes)
+    fn json_event(&mut self, event: Event) -> io::Result<()> {
+        let (kind, record) = match event {
+            Event::Match(record) => ("match", record),
+            Event::Context(record) => ("context", record),
+            Event::Break => return Ok(()), // a reader of JSON can see the gap in the line numbers
+        };
+        if !self.began {
+            self.began = true;
+            self.printed = true;
+            writeln!(
+                self.out,
+                "{{\"type\":\"begin\",\"data\":{{\"path\":{}}}}}",
+                json::data(self.name.as_bytes())
+            )?;
+        }
+        self.matches += record.spans.len() as u64;
+
+        let submatches: Vec<String> = record
+            .spans
+            .iter()
+            .map(|span| {
+                let text = &record.line[span.clone()];
+                let distance = match &self.fuzzy {
+                    Some(fuzzy) => format!(",\"distance\":{}", fuzzy.distance(text)),
+                    None => String::new(),
+                };
+                let (start, end) = if record.line.as_bytes() == record.raw {
+                    (span.start, span.end)
+                } else {
+                    (
+                        json::raw_offset(record.raw, span.start),
+                        json::raw_offset(record.raw, span.end),
+                    )
+                };
+                format!(
+                    "{{\"match\":{{\"text\":{}}},\"start\":{},\"end\":{}{}}}",
+                    json::string(text),
+                    start,
+                    end,
+                    distance
+                )
+            })
+            .collect();
+        writeln!(
+            self.out,
+            "{{\"type\":\"{}\",\"data\":{{\"path\":{},\"lines\":{},\"line_number\":{},\"last_line_number\":{},\"absolute_offset\":{},\"submatches\":[{}]}}}}",
+            kind,
+            json::data(self.name.as_bytes()),
+            json::data(record.raw),
+            record.line_number,
+            record.last_line_number,
+            record.byte_offset,
+            submatches.join(",")
+        )
+    }
+
+    // the last line of --json, with the numbers of every file together
+    pub fn summary(&mut self, stats: &Stats, elapsed: Duration) -> io::Result<()> {
+        writeln!(
+            self.out,
+            "{{\"type\":\"summary\",\"data\":{{\"stats\":{{\"searches\":{},\"searches_with_match\":{},\"matched_lines\":{}}},\"elapsed_secs\":{}}}}}",
+            stats.searches,
+            stats.searches_with_match,
+            stats.matched_lines,
+            elapsed.as_secs_f64()
+        )
+    }
+
+    fn line(&mut self, record: &Record, sep: &str) -> io::Result<()> {
+        // a --multiline match can cover more than one line, every one of them gets a prefix
+        let mut start = 0;
+        for (i, part) in record.line.split('\n').enumerate() {
+            let end = start + part.len();
+            self.prefix(record, i, start, sep)?;
+            // the last line lost its line ending already, the others still have a \r of a \r\n
+            let shown = match part.strip_suffix('\r') {
+                Some(part) if end < record.line.len() => start + part.len(),
+                _ => end,
+            };
+            self.text(record.line, start..shown, &record.spans)?;
+            if end == record.line.len() {
+                self.distances(record)?;
+            }
+            writeln!(self.out)?;
+            start = end + 1;
+        }
+        Ok(())
+    }
+
+    // what comes in front of line i of a record, start is where that line starts in it
+    fn prefix(&mut self, record: &Record, i: usize, start: usize, sep: &str) -> io::Result<()> {
+        if self.show_name {
+            self.name()?;
+            self.separator(sep)?;
+        }
+        if self.line_number {
+            self.paint(NUMBER, record.line_number + i as u64)?;
+            self.separator(sep)?;
+        }
+        // columns are 1 based and counted in bytes, context lines have no match so no column
+        if self.column {
+            if let (0, Some(span)) = (i, record.spans.first()) {
+                self.paint(NUMBER, span.start + 1)?;
+            }
+            self.separator(sep)?;
+        }
+        if self.byte_offset {
+            self.paint(NUMBER, record.byte_offset + start as u64)?;
+            self.separator(sep)?;
+        }
+        Ok(())
+    }
+
+    // --fuzzy: after a tab, what every match looks like and how many edits away it is
+    fn distances(&mut self, record: &Record) -> io::Result<()> {
+        let Some(fuzzy) = &self.fuzzy else {
+            return Ok(());
+        };
+        let found: Vec<(&str, u32)> = record
+            .spans
+            .iter()
+            .map(|span| {
+                let text = &record.line[span.clone()];
+                (text, fuzzy.distance(text))
+            })
+            .collect();
+        for (i, (text, distance)) in found.into_iter().enumerate() {
+            write!(self.out, "{}", if i == 0 { "\t" } else { ", " })?;
+            self.paint(MATCH, text)?;
+            write!(self.out, " (distance {})", distance)?;
+        }
+        Ok(())
+    }
+
+    // vimgrep lines always start with the file name, even for a single file
+    // with --multiline every match is shown on the line it starts on
+    fn vimgrep_line(&mut self, record: &Record) -> io::Result<()> {
+        for span in &record.spans {
+            let before = &record.line[..span.start];
+            let down = before.matches('\n').count() as u64;
+            let start = before.rfind('\n').map_or(0, |i| i + 1);
+            let end = record.line[start..]
+                .find('\n')
+                .map_or(record.line.len(), |i| start + i);
+            let end = start + record.line[start..end].trim_end_matches('\r').len();
+            self.name()?;
+            self.separator(":")?;
+            self.paint(NUMBER, record.line_number + down)?;
+            self.separator(":")?;
+            self.paint(NUMBER, span.start - start + 1)?;
+            self.separator(":")?;
+            self.text(record.line, start..end, &record.spans)?;
+            writeln!(self.out)?;
+        }
+        Ok(())
+    }
+
+    // the part range of line, with every span in it highlighted when colors are on
+    // the spans point into the original line, so case insensitive matches keep their case
+    fn text(&mut self, line: &str, range: Range<usize>, spans: &[Range<usize>]) -> io::Result<()> {
+        if !self.color {
+            return write!(self.out, "{}", &line[range]);
+        }
+        let mut at = range.start;
+        for span in merge(spans) {
+            let (start, end) = (span.start.max(at), span.end.min(range.end));
+            if start >= end {
+                continue; // not on this part of the line
+            }
+            write!(self.out, "{}", &line[at..start])?;
+            self.paint(MATCH, &line[start..end])?;
+            at = end;
+        }
+        write!(self.out, "{}", &line[at..range.end])
+    }
+
+    fn name(&mut self) -> io::Result<()> {
+        if self.color {
+            write!(self.out, "{}{}{}", NAME, self.name, RESET)
+        } else {
+            write!(self.out, "{}", self.name)
+        }
+    }
+
+    fn separator(&mut self, sep: &str) -> io::Result<()> {
+        self.paint(SEPARATOR, sep)
+    }
+
+    fn paint(&mut self, code: &str, text: impl Display) -> io::Result<()> {
+        if self.color {
+            write!(self.out, "{}{}{}", code, text, RESET)
+        } else {
+            write!(self.out, "{}", text)
+        }
+    }
+}
+
+// sorted spans without empty or overlapping ones, so every byte is highlighted at most once
+fn merge(spans: &[Range<usize>]) -> Vec<Range<usize>> {
+    let mut sorted: Vec<Range<usize>> = spans.iter().filter(|s| !s.is_empty()).cloned().collect();
+    sorted.sort_by_key(|span| span.start);
+
+    let mut merged: Vec<Range<usize>> = Vec::with_capacity(sorted.len());
+    for span in sorted {
+        match merged.last_mut() {
+            Some(last) if span.start <= last.end => last.end = last.end.max(span.end),
+            _ => merged.push(span),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::search_str;
+    use crate::Matcher;
+
+    fn print(config: &Config, show_name: bool, contents: &str) -> String {
+        let matcher = Matcher::new(config).unwrap();
+        let mut printer = Printer::new(Vec::new(), config);
+        printer.begin_file("p", show_name);
+        for record in search_str(&matcher, contents) {
+            printer.event(Event::Match(record)).unwrap();
+        }
+        String::from_utf8(printer.out).unwrap()
+    }
+
+    fn config(args: &[&str]) -> Config {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        Config::new(&args).unwrap()
+    }
+
+    #[test]
+    fn prefixes() {
+        let contents = "Rust:\nTrust me, rust.";
+
+        let config = config(&["minigrep", "-n", "-b", "--column", "ust", "poem.txt"]);
+        assert_eq!(
+            "p:1:2:0:Rust:\np:2:3:6:Trust me, rust.\n",
+            print(&config, true, contents)
+        );
+    }
+
+    #[test]
+    fn vimgrep_prints_every_match() {
+        let contents = "Rust:\nTrust me, rust.";
+
+        let config = config(&["minigrep", "--vimgrep", "ust", "poem.txt"]);
+        assert_eq!(
+            "p:1:2:Rust:\np:2:3:Trust me, rust.\np:2:12:Trust me, rust.\n",
+            print(&config, true, contents)
+        );
+    }
+
+    #[test]
+    fn summary_modes() {
+        let end = |flag: &str, show_name: bool, count: u64| {
+            let config = config(&["minigrep", flag, "ust", "poem.txt"]);
+            let mut printer = Printer::new(Vec::new(), &config);
+            printer.begin_file("p", show_name);
+            printer.end_file(count).unwrap();
+            String::from_utf8(printer.out).unwrap()
+        };
+
+        assert_eq!("p:3\n", end("-c", true, 3));
+        assert_eq!("0\n", end("-c", false, 0));
+        assert_eq!("p\n", end("-l", false, 1));
+        assert_eq!("", end("-l", true, 0));
+        assert_eq!("p\n", end("-L", false, 0));
+        assert_eq!("", end("-L", true, 2));
+        assert_eq!("", end("-q", true, 2));
+
+        // in the summary modes the lines themselves are not printed
+        let config = config(&["minigrep", "-c", "ust", "poem.txt"]);
+        assert_eq!("", print(&config, true, "Rust:"));
+    }
+
+    #[test]
+    fn highlights_original_case() {
+        let config = config(&["minigrep", "-i", "-n", "rust", "poem.txt"]);
+        let matcher = Matcher::new(&config).unwrap();
+        let mut printer = Printer::new(Vec::new(), &config).with_color(true);
+        printer.begin_file("p", true);
+        for record in search_str(&matcher, "Trust me, RUST.") {
+            printer.event(Event::Match(record)).unwrap();
+        }
+        assert_eq!(
+            "\x1b[35mp\x1b[0m\x1b[36m:\x1b[0m\x1b[32m1\x1b[0m\x1b[36m:\x1b[0m\
+             T\x1b[1;31mrust\x1b[0m me, \x1b[1;31mRUST\x1b[0m.\n",
+            String::from_utf8(printer.out).unwrap()
+        );
+    }
+
+    #[test]
+    fn color_choice() {
+        let no_color = || Some(OsString::from("1"));
+        assert!(use_color(ColorChoice::Auto, true, None));
+        assert!(!use_color(ColorChoice::Auto, false, None));
+        assert!(!use_color(ColorChoice::Auto, true, no_color()));
+        // an empty NO_COLOR does not count
+        assert!(use_color(ColorChoice::Auto, true, Some(OsString::new())));
+        assert!(use_color(ColorChoice::Always, false, no_color()));
+        assert!(!use_color(ColorChoice::Never, true, None));
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn multiline_records_print_every_line() {
+        let config = config(&["minigrep", "-n", "-U", "-b", "unused", "poem.txt"]);
+        let mut printer = Printer::new(Vec::new(), &config).with_color(true);
+        printer.begin_file("p", false);
+        let record = Record {
+            line_number: 3,
+            last_line_number: 4,
+            byte_offset: 10,
+            line: "fn add(\r\n    a: i32,",
+            raw: b"fn add(\r\n    a: i32,",
+            spans: vec![3..15],
+        };
+        printer.event(Event::Match(record)).unwrap();
+        assert_eq!(
+            "\x1b[32m3\x1b[0m\x1b[36m:\x1b[0m\x1b[32m10\x1b[0m\x1b[36m:\x1b[0m\
+             fn \x1b[1;31madd(\x1b[0m\n\
+             \x1b[32m4\x1b[0m\x1b[36m:\x1b[0m\x1b[32m19\x1b[0m\x1b[36m:\x1b[0m\
+             \x1b[1;31m    a:\x1b[0m i32,\n",
+            String::from_utf8(printer.out).unwrap()
+        );
+    }
+
+    #[test]
+    fn json_events() {
+        let config = config(&["minigrep", "--json", "-A1", "frog", "poem.txt"]);
+        let matcher = Matcher::new(&config).unwrap();
+        let mut printer = Printer::new(Vec::new(), &config);
+        printer.begin_file("p", false);
+        let contents: &[u8] = b"caf\xe9 frog\nnext \"line\"\n";
+        let options = crate::search::Options {
+            context: crate::search::Context {
+                before: 0,
+                after: 1,
+            },
+            ..Default::default()
+        };
+        let count = crate::search::search_reader_with(&matcher, contents, &options, |event| {
+            printer.event(event)
+        })
+        .unwrap();
+        printer.end_file(count).unwrap();
+        // a file without anything to show leaves no trace
+        printer.begin_file("q", false);
+        printer.end_file(0).unwrap();
+
+        let lines: Vec<String> = String::from_utf8(printer.out)
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect();
+        assert_eq!(
+            vec![
+                r#"{"type":"begin","data":{"path":{"text":"p"}}}"#,
+                // the line is not UTF-8 so it comes as bytes, the spans point into those bytes
+                r#"{"type":"match","data":{"path":{"text":"p"},"lines":{"bytes":"Y2Fm6SBmcm9n"},"line_number":1,"last_line_number":1,"absolute_offset":0,"submatches":[{"match":{"text":"frog"},"start":5,"end":9}]}}"#,
+                r#"{"type":"context","data":{"path":{"text":"p"},"lines":{"text":"next \"line\""},"line_number":2,"last_line_number":2,"absolute_offset":10,"submatches":[]}}"#,
+                r#"{"type":"end","data":{"path":{"text":"p"},"stats":{"matched_lines":1,"matches":1}}}"#,
+            ],
+            lines
+        );
+
+        // what a consumer does with it: decode the bytes and slice them
+        let bytes = unbase64("Y2Fm6SBmcm9n");
+        assert_eq!(b"caf\xe9 frog", &bytes[..]);
+        assert_eq!(b"frog", &bytes[5..9]);
+    }
+
+    // just enough base64 decoding for the test above
+    fn unbase64(text: &str) -> Vec<u8> {
+        const BASE64: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let sextets: Vec<u32> = text
+            .bytes()
+            .filter(|&b| b != b'=')
+            .map(|b| BASE64.iter().position(|&c| c == b).unwrap() as u32)
+            .collect();
+        let mut out = Vec::new();
+        for chunk in sextets.chunks(4) {
+            let n = chunk
+                .iter()
+                .enumerate()
+                .fold(0, |n, (i, s)| n | s << (18 - 6 * i));
+            out.extend(n.to_be_bytes()[1..chunk.len()].iter());
+        }
+        out
+    }
+
+    #[test]
+    fn merges_overlapping_spans() {
+        assert_eq!(vec![1..6, 8..9], merge(&[4..6, 8..9, 1..5, 3..3]));
+    }
+}
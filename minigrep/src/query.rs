@@ -0,0 +1,503 @@
+// --query: a small boolean language over plain terms
+//
+//     timeout AND NOT retry          lines with timeout but without retry
+//     unsafe NEAR/5 transmute        unsafe at most 5 lines away from transmute (or the other way)
+//     (panic OR "fatal error") db    two terms next to each other are ANDed
+//
+// NOT binds tightest, then NEAR/k, then AND, then OR, parentheses group like anywhere else
+// the operators are only operators in uppercase, to search for the word AND put it in quotes
+// every term gets its own Matcher, so -i, -S, -E and -w work for the terms the same as always
+use std::ops::Range;
+
+use crate::index::Required;
+use crate::{Config, Matcher, MinigrepError};
+
+// what a query is about, single lines or whole files (--query-scope)
+// at file scope "a AND NOT b" is a file with a somewhere and b nowhere
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Scope {
+    #[default]
+    Line,
+    File,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    // an index into the terms of the Query
+    Term(usize),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    // both sides at most this many lines apart, 0 means on the same line
+    Near(Box<Expr>, Box<Expr>, usize),
+}
+
+// a parsed query, the terms are still plain strings
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    expr: Expr,
+    terms: Vec<String>,
+}
+
+impl Query {
+    pub fn parse(text: &str) -> Result<Query, MinigrepError> {
+        let mut parser = Parser {
+            text,
+            tokens: tokenize(text)?,
+            at: 0,
+            terms: Vec::new(),
+        };
+        let expr = parser.or()?;
+        // or() only stops early at a ) it has no ( for
+        if let Some(token) = parser.tokens.get(parser.at) {
+            return Err(error(
+                text,
+                token.start,
+                "unexpected ), there is no ( for it",
+            ));
+        }
+        Ok(Query {
+            expr,
+            terms: parser.terms,
+        })
+    }
+
+    // every term in the order it was written, smart case looks at these
+    pub fn terms(&self) -> &[String] {
+        &self.terms
+    }
+
+    // what a file has to contain for the query to match in it (search --index), term says
+    // what a single term needs
+    pub fn required(&self, term: impl Fn(&str) -> Required) -> Required {
+        self.required_by(&self.expr, &term)
+    }
+
+    fn required_by(&self, expr: &Expr, term: &impl Fn(&str) -> Required) -> Required {
+        match expr {
+            Expr::Term(i) => term(&self.terms[*i]),
+            // a file without the term is just what NOT wants
+            Expr::Not(_) => Required::Any,
+            Expr::And(a, b) | Expr::Near(a, b, _) => {
+                Required::and(vec![self.required_by(a, term), self.required_by(b, term)])
+            }
+            Expr::Or(a, b) => {
+                Required::or(vec![self.required_by(a, term), self.required_by(b, term)])
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Kind {
+    Term(String),
+    And,
+    Or,
+    Not,
+    Near(usize),
+    Open,
+    Close,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: Kind,
+    // byte offset in the query, for the error messages
+    start: usize,
+}
+
+// words are split at whitespace, parentheses and quotes
+// inside quotes \" and \\ are a quote and a backslash, any other backslash stays as it is
+fn tokenize(text: &str) -> Result<Vec<Token>, MinigrepError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let kind = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Kind::Open,
+            ')' => Kind::Close,
+            '"' => {
+                let mut term = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) if matches!(chars.peek(), Some((_, '"' | '\\'))) => {
+                            term.extend(chars.next().map(|(_, c)| c));
+                        }
+                        Some((_, c)) => term.push(c),
+                        None => return Err(error(text, start, "this quote is never closed")),
+                    }
+                }
+                Kind::Term(term)
+            }
+            c => {
+                let mut word = c.to_string();
+                while let Some(&(_, c)) = chars.peek() {
+                    if c.is_whitespace() || matches!(c, '(' | ')' | '"') {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                match word.as_str() {
+                    "AND" => Kind::And,
+                    "OR" => Kind::Or,
+                    "NOT" => Kind::Not,
+                    _ => match word.strip_prefix("NEAR") {
+                        // NEARBY is just a word, NEAR and NEAR/x are a NEAR without a distance
+                        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+                            let lines = rest.strip_prefix('/').and_then(|n| n.parse().ok());
+                            Kind::Near(lines.ok_or_else(|| {
+                                error(text, start, "NEAR needs a number of lines, like NEAR/5")
+                            })?)
+                        }
+                        _ => Kind::Term(word),
+                    },
+                }
+            }
+        };
+        tokens.push(Token { kind, start });
+    }
+    Ok(tokens)
+}
+
+// recursive descent, one function per level of precedence
+struct Parser<'a> {
+    text: &'a str,
+    tokens: Vec<Token>,
+    at: usize,
+    terms: Vec<String>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Kind> {
+        self.tokens.get(self.at).map(|token| &token.kind)
+    }
+
+    fn eat(&mut self, kind: &Kind) -> bool {
+        let found = self.peek() == Some(kind);
+        self.at += found as usize;
+        found
+    }
+
+    fn or(&mut self) -> Result<Expr, MinigrepError> {
+        let mut left = self.and()?;
+        while self.eat(&Kind::Or) {
+            left = Expr::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr, MinigrepError> {
+        let mut left = self.near()?;
+        loop {
+            match self.peek() {
+                Some(Kind::And) => self.at += 1,
+                // two terms next to each other, the AND is left out
+                Some(Kind::Term(_) | Kind::Open | Kind::Not) => {}
+                _ => return Ok(left),
+            }
+            left = Expr::And(Box::new(left), Box::new(self.near()?));
+        }
+    }
+
+    fn near(&mut self) -> Result<Expr, MinigrepError> {
+        let mut left = self.not()?;
+        while let Some(&Kind::Near(lines)) = self.peek() {
+            self.at += 1;
+            left = Expr::Near(Box::new(left), Box::new(self.not()?), lines);
+        }
+        Ok(left)
+    }
+
+    fn not(&mut self) -> Result<Expr, MinigrepError> {
+        if self.eat(&Kind::Not) {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.term()
+    }
+
+    fn term(&mut self) -> Result<Expr, MinigrepError> {
+        let Some(token) = self.tokens.get(self.at).cloned() else {
+            return Err(self.expected_term(self.text.len(), "the end of the query"));
+        };
+        match token.kind {
+            Kind::Term(term) => {
+                self.at += 1;
+                self.terms.push(term);
+                Ok(Expr::Term(self.terms.len() - 1))
+            }
+            Kind::Open => {
+                self.at += 1;
+                let inner = self.or()?;
+                if !self.eat(&Kind::Close) {
+                    return Err(error(self.text, token.start, "this ( is never closed"));
+                }
+                Ok(inner)
+            }
+            kind => Err(self.expected_term(token.start, &describe(&kind))),
+        }
+    }
+
+    // a term was missing, found is what we got instead
+    fn expected_term(&self, at: usize, found: &str) -> MinigrepError {
+        let after = match self.at.checked_sub(1) {
+            Some(i) => format!(" after {}", describe(&self.tokens[i].kind)),
+            None => String::new(),
+        };
+        error(
+            self.text,
+            at,
+            &format!("expected a term{}, found {}", after, found),
+        )
+    }
+}
+
+fn describe(kind: &Kind) -> String {
+    match kind {
+        Kind::Term(term) => format!("\"{}\"", term),
+        Kind::And => "AND".to_string(),
+        Kind::Or => "OR".to_string(),
+        Kind::Not => "NOT".to_string(),
+        Kind::Near(lines) => format!("NEAR/{}", lines),
+        Kind::Open => "(".to_string(),
+        Kind::Close => ")".to_string(),
+    }
+}
+
+// the message, and the query with a ^ under the char at byte offset at
+fn error(text: &str, at: usize, message: &str) -> MinigrepError {
+    let column = text[..at].chars().count() + 1;
+    MinigrepError::InvalidPattern(format!(
+        "{} at column {} of the query\n  {}\n  {}^",
+        message,
+        column,
+        text,
+        " ".repeat(column - 1)
+    ))
+}
+
+// a Query with a Matcher for every term, ready to search with
+pub struct QueryMatcher {
+    query: Query,
+    matchers: Vec<Matcher>,
+    // terms that are not under a NOT, only those are highlighted
+    positive: Vec<bool>,
+    pub scope: Scope,
+}
+
+impl QueryMatcher {
+    // the terms are searched with the case, regex and word options of config
+    pub fn new(text: &str, scope: Scope, config: &Config) -> Result<QueryMatcher, MinigrepError> {
+        let query = Query::parse(text)?;
+        let matchers = query
+            .terms
+            .iter()
+            .map(|term| {
+                Matcher::new(&Config {
+                    patterns: vec![term.clone()],
+                    case_sensitive: config.case_sensitive,
+                    regex: config.regex,
+                    word_regexp: config.word_regexp,
+                    line_regexp: config.line_regexp,
+                    ..Config::default()
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        let mut positive = vec![false; query.terms.len()];
+        mark_positive(&query.expr, true, &mut positive);
+        Ok(QueryMatcher {
+            query,
+            matchers,
+            positive,
+            scope,
+        })
+    }
+
+    // which of the lines the query selects, at line scope
+    pub fn select(&self, lines: &[&str]) -> Vec<bool> {
+        self.lines(&self.query.expr, &self.hits(lines))
+    }
+
+    // if the query holds for lines as a whole, at file scope
+    pub fn file(&self, lines: &[&str]) -> bool {
+        self.holds(&self.query.expr, &self.hits(lines))
+    }
+
+    // a single line on its own, NEAR can only see the same line then
+    pub fn is_match(&self, line: &str) -> bool {
+        self.select(&[line])[0]
+    }
+
+    // where the terms that are not under a NOT are in line, for highlighting
+    // a selected line can have no spans at all, like every line of NOT retry
+    pub fn find_spans(&self, line: &str) -> Vec<Range<usize>> {
+        let mut spans: Vec<Range<usize>> = self
+            .matchers
+            .iter()
+            .zip(&self.positive)
+            .filter(|(_, &positive)| positive)
+            .flat_map(|(matcher, _)| matcher.find_spans(line))
+            .collect();
+        spans.sort_by_key(|span| (span.start, span.end));
+        // two terms that overlap are highlighted as one
+        let mut merged: Vec<Range<usize>> = Vec::with_capacity(spans.len());
+        for span in spans {
+            match merged.last_mut() {
+                Some(last) if span.start < last.end => last.end = last.end.max(span.end),
+                _ => merged.push(span),
+            }
+        }
+        merged
+    }
+
+    // hits[term][line], every term is only searched once per line
+    fn hits(&self, lines: &[&str]) -> Vec<Vec<bool>> {
+        self.matchers
+            .iter()
+            .map(|matcher| lines.iter().map(|line| matcher.is_match(line)).collect())
+            .collect()
+    }
+
+    fn lines(&self, expr: &Expr, hits: &[Vec<bool>]) -> Vec<bool> {
+        let both = |a: &Expr, b: &Expr, f: fn(bool, bool) -> bool| {
+            let (a, b) = (self.lines(a, hits), self.lines(b, hits));
+            a.into_iter().zip(b).map(|(a, b)| f(a, b)).collect()
+        };
+        match expr {
+            Expr::Term(i) => hits[*i].clone(),
+            Expr::Not(inner) => self.lines(inner, hits).into_iter().map(|b| !b).collect(),
+            Expr::And(a, b) => both(a, b, |a, b| a && b),
+            Expr::Or(a, b) => both(a, b, |a, b| a || b),
+            Expr::Near(a, b, distance) => {
+                let (a, b) = (self.lines(a, hits), self.lines(b, hits));
+                let (near_a, near_b) = (within(&a, *distance), within(&b, *distance));
+                (0..a.len())
+                    .map(|i| (a[i] && near_b[i]) || (b[i] && near_a[i]))
+                    .collect()
+            }
+        }
+    }
+
+    fn holds(&self, expr: &Expr, hits: &[Vec<bool>]) -> bool {
+        match expr {
+            Expr::Term(i) => hits[*i].contains(&true),
+            Expr::Not(inner) => !self.holds(inner, hits),
+            Expr::And(a, b) => self.holds(a, hits) && self.holds(b, hits),
+            Expr::Or(a, b) => self.holds(a, hits) || self.holds(b, hits),
+            // NEAR is about lines even in a file, somewhere the two have to be close
+            Expr::Near(..) => self.lines(expr, hits).contains(&true),
+        }
+    }
+}
+
+fn mark_positive(expr: &Expr, positive: bool, out: &mut [bool]) {
+    match expr {
+        Expr::Term(i) => out[*i] |= positive,
+        Expr::Not(inner) => mark_positive(inner, !positive, out),
+        Expr::And(a, b) | Expr::Or(a, b) | Expr::Near(a, b, _) => {
+            mark_positive(a, positive, out);
+            mark_positive(b, positive, out);
+        }
+    }
+}
+
+// for every line, is one of the lines at most distance away from it set
+// with a running count of the set lines every window is a single subtraction
+fn within(set: &[bool], distance: usize) -> Vec<bool> {
+    let mut before = Vec::with_capacity(set.len() + 1);
+    before.push(0);
+    for (i, &b) in set.iter().enumerate() {
+        before.push(before[i] + b as usize);
+    }
+    (0..set.len())
+        .map(|i| before[(i + distance + 1).min(set.len())] > before[i.saturating_sub(distance)])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matcher(text: &str, case_sensitive: bool) -> QueryMatcher {
+        let config = Config {
+            case_sensitive,
+            ..Config::default()
+        };
+        QueryMatcher::new(text, Scope::Line, &config).unwrap()
+    }
+
+    fn selected<'a>(text: &str, lines: &[&'a str]) -> Vec<&'a str> {
+        let query = matcher(text, true);
+        lines
+            .iter()
+            .zip(query.select(lines))
+            .filter(|(_, selected)| *selected)
+            .map(|(line, _)| *line)
+            .collect()
+    }
+
+    #[test]
+    fn precedence_and_quotes() {
+        let lines = ["timeout", "timeout, retry", "retry", "AND NOT", "error db"];
+        assert_eq!(vec!["timeout"], selected("timeout AND NOT retry", &lines));
+        // NOT before AND before OR
+        assert_eq!(
+            vec!["timeout", "retry"],
+            selected("timeout AND NOT retry OR retry AND NOT timeout", &lines)
+        );
+        assert_eq!(
+            vec!["timeout, retry"],
+            selected("(timeout OR error) retry", &lines)
+        );
+        assert_eq!(vec!["AND NOT"], selected(r#""AND" "NOT""#, &lines));
+        assert_eq!(vec!["error db"], selected(r#""error db""#, &lines));
+
+        let query = Query::parse(r#"a "b \"c\" \d" NEARBY"#).unwrap();
+        assert_eq!(vec!["a", r#"b "c" \d"#, "NEARBY"], query.terms());
+    }
+
+    #[test]
+    fn near_at_line_and_file_scope() {
+        let lines = [
+            "unsafe {",
+            "    let x = 1;",
+            "    transmute(x)",
+            "}",
+            "unsafe fn other() {}",
+        ];
+        let query = matcher("unsafe NEAR/2 transmute", true);
+        assert_eq!(vec![true, false, true, false, true], query.select(&lines));
+        assert!(query.file(&lines));
+        assert!(!matcher("unsafe NEAR/1 transmute", true).file(&lines));
+        assert!(!matcher("unsafe NEAR/0 transmute", true).file(&lines));
+
+        // at file scope the terms are about the whole file, no line has both
+        assert!(matcher("other AND transmute", true).file(&lines));
+        assert!(!matcher("other AND NOT transmute", true).file(&lines));
+        assert!(matcher("NOT (other transmute)", true).select(&lines)[0]);
+
+        // only the terms outside of a NOT are highlighted, overlapping ones as one
+        let query = matcher("NOT x AND (unsa OR safe)", false);
+        assert_eq!(vec![0..6], query.find_spans("UNSAFE fn other() {}"));
+        assert!(query.find_spans("let x = 1").is_empty());
+    }
+
+    #[test]
+    fn helpful_errors() {
+        let err = |text: &str| Query::parse(text).unwrap_err().to_string();
+        assert_eq!(
+            "invalid pattern: expected a term after AND, found OR at column 7 of the query\n  \
+             a AND OR b\n        ^",
+            err("a AND OR b")
+        );
+        assert!(err("").contains("expected a term, found the end of the query at column 1"));
+        assert!(err("a NOT").contains("expected a term after NOT, found the end of the query"));
+        assert!(err("(a OR b").contains("this ( is never closed at column 1"));
+        assert!(err("a) b").contains("unexpected ), there is no ( for it at column 2"));
+        assert!(err("a ()").contains("expected a term after (, found )"));
+        assert!(err("a NEAR b").contains("NEAR needs a number of lines, like NEAR/5"));
+        assert!(err("a NEAR/x b").contains("NEAR needs a number of lines"));
+        assert!(err(r#"a "b"#).contains("this quote is never closed at column 3"));
+    }
+}
@@ -0,0 +1,215 @@
+// --in-place and --dry-run: instead of printing the matching lines we rewrite whole files
+// files are written next to the original first and then renamed over it, so a crash
+// half way through never leaves a half written file behind
+use std::borrow::Cow;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::matcher::Matcher;
+
+// lines of context around every change in the diff, the same as diff -u
+const CONTEXT: usize = 3;
+
+// a file before and after the replacement, one entry per line of the old file
+// every entry still has its line ending, a replacement with \n in it makes a new entry span many lines
+pub struct Rewrite<'a> {
+    old: Vec<&'a str>,
+    new: Vec<Cow<'a, str>>,
+}
+
+impl<'a> Rewrite<'a> {
+    pub fn new(
+        matcher: &Matcher,
+        contents: &'a str,
+        replacement: &str,
+        expand: bool,
+    ) -> Rewrite<'a> {
+        let old: Vec<&str> = contents.split_inclusive('\n').collect();
+        let new = old
+            .iter()
+            .map(|&raw| {
+                let line = raw.trim_end_matches(['\n', '\r']);
+                if !matcher.is_match(line) {
+                    return Cow::Borrowed(raw);
+                }
+                let (mut replaced, _) = matcher.replace(line, replacement, expand);
+                replaced.push_str(&raw[line.len()..]);
+                if replaced == raw {
+                    Cow::Borrowed(raw)
+                } else {
+                    Cow::Owned(replaced)
+                }
+            })
+            .collect();
+        Rewrite { old, new }
+    }
+
+    // how many lines are different
+    pub fn changed(&self) -> usize {
+        self.changed_lines().count()
+    }
+
+    fn changed_lines(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.old.len()).filter(|&i| self.old[i] != self.new[i])
+    }
+
+    pub fn contents(&self) -> String {
+        self.new.concat()
+    }
+
+    // what diff -u would print for the old and the new file
+    pub fn unified_diff(&self, name: &str) -> String {
+        let mut diff = String::new();
+        if self.changed() == 0 {
+            return diff;
+        }
+        diff.push_str(&format!("--- {}\n+++ {}\n", name, name));
+
+        // changes closer together than two contexts share one hunk
+        let changed: Vec<usize> = self.changed_lines().collect();
+        let mut hunks: Vec<(usize, usize)> = Vec::new();
+        for &i in &changed {
+            match hunks.last_mut() {
+                Some((_, last)) if i <= *last + 2 * CONTEXT => *last = i,
+                _ => hunks.push((i, i)),
+            }
+        }
+
+        for (first, last) in hunks {
+            let start = first.saturating_sub(CONTEXT);
+            let end = (last + CONTEXT + 1).min(self.old.len());
+            let new_start: usize = self.new[..start].iter().map(|text| line_count(text)).sum();
+            let new_len: usize = self.new[start..end]
+                .iter()
+                .map(|text| line_count(text))
+                .sum();
+            diff.push_str(&format!(
+                "@@ -{} +{} @@\n",
+                range(start, end - start),
+                range(new_start, new_len)
+            ));
+            for i in start..end {
+                if self.old[i] == self.new[i] {
+                    push_lines(&mut diff, ' ', self.old[i]);
+                } else {
+                    push_lines(&mut diff, '-', self.old[i]);
+                    push_lines(&mut diff, '+', &self.new[i]);
+                }
+            }
+        }
+        diff
+    }
+}
+
+// diff writes line numbers starting at 1, and an empty range points at the line before it
+fn range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, len),
+    }
+}
+
+fn line_count(text: &str) -> usize {
+    text.split_inclusive('\n').count()
+}
+
+fn push_lines(diff: &mut String, prefix: char, text: &str) {
+    for line in text.split_inclusive('\n') {
+        diff.push(prefix);
+        diff.push_str(line);
+        if !line.ends_with('\n') {
+            diff.push_str("\n\\ No newline at end of file\n");
+        }
+    }
+}
+
+// replaces path with contents: write a temp file in the same directory, then rename it over path
+// rename within one directory is atomic, readers see either the old or the new file
+// with backup the old file is kept as path.bak
+// a symlink stays a symlink, it is the file it points to that gets replaced
+pub fn write_atomic(path: &Path, contents: &[u8], backup: bool) -> io::Result<()> {
+    let path = &fs::canonicalize(path)?;
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file"))?
+        .to_string_lossy()
+        .into_owned();
+    let temp = path.with_file_name(format!(".{}.minigrep-{}", name, process::id()));
+
+    let result = (|| {
+        let mut file = File::create_new(&temp)?;
+        file.write_all(contents)?;
+        // the new file gets the permissions of the old one, a script stays executable
+        file.set_permissions(fs::metadata(path)?.permissions())?;
+        file.sync_all()?;
+        if backup {
+            fs::copy(path, backup_path(path, &name))?;
+        }
+        fs::rename(&temp, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+fn backup_path(path: &Path, name: &str) -> PathBuf {
+    path.with_file_name(format!("{}.bak", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_of_a_rewrite() {
+        let contents =
+            "one\ntwo\nthree\nfour\nfive\nsix\nseven\neight\nnine\nten\neleven\ntwelve\nadd";
+        let matcher = Matcher::Sensitive("t".to_string());
+        let rewrite = Rewrite::new(&matcher, contents, "T", false);
+        assert_eq!(5, rewrite.changed());
+        assert!(rewrite.contents().starts_with("one\nTwo\nThree\n"));
+
+        let matcher = Matcher::Sensitive("add".to_string());
+        let rewrite = Rewrite::new(&matcher, contents, "sum\nsum", false);
+        assert_eq!(
+            "--- f.txt\n+++ f.txt\n@@ -10,4 +10,5 @@\n ten\n eleven\n twelve\n\
+             -add\n\\ No newline at end of file\n+sum\n+sum\n\\ No newline at end of file\n",
+            rewrite.unified_diff("f.txt")
+        );
+
+        let rewrite = Rewrite::new(&matcher, "nothing\n", "sum", false);
+        assert_eq!(0, rewrite.changed());
+        assert_eq!("", rewrite.unified_diff("f.txt"));
+    }
+
+    #[test]
+    fn writes_atomically_with_a_backup() {
+        let dir = std::env::temp_dir().join(format!("minigrep_replace_{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("a.txt");
+        fs::write(&path, "old\n").unwrap();
+
+        write_atomic(&path, b"new\n", true).unwrap();
+        assert_eq!("new\n", fs::read_to_string(&path).unwrap());
+        assert_eq!("old\n", fs::read_to_string(dir.join("a.txt.bak")).unwrap());
+        // no temp file is left behind
+        assert_eq!(2, fs::read_dir(&dir).unwrap().count());
+
+        // through a symlink the file it points to is written, the link stays
+        let link = dir.join("link.txt");
+        std::os::unix::fs::symlink(&path, &link).unwrap();
+        write_atomic(&link, b"newer\n", false).unwrap();
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!("newer\n", fs::read_to_string(&path).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,903 @@
+// streaming search, we read one line at a time from any BufRead (a file, stdin, a byte slice...)
+// so memory use only depends on the longest line and not on the size of the file
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::io::{self, BufRead};
+use std::ops::Range;
+
+use crate::matcher::Matcher;
+use crate::query::{QueryMatcher, Scope};
+
+// how many lines around every match should be printed, like grep -B and -A
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Context {
+    pub before: usize,
+    pub after: usize,
+}
+
+// everything that changes which lines are sent, besides the Matcher itself
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Options {
+    pub context: Context,
+    // send the lines that dont match instead, like grep -v
+    pub invert: bool,
+    // stop reading after this many selected lines, -l and -q only need to know about the first one
+    pub max_count: Option<u64>,
+    // lines and bytes that come before the input, --watch only searches what was added to a file
+    // and its line numbers and byte offsets still have to count from the start of the file
+    pub lines_before: u64,
+    pub bytes_before: u64,
+    // lines up to this line number were searched before (by --watch), they are only read again
+    // as before context and for -U and NEAR/k matches that reach into the new lines
+    pub searched_lines: u64,
+}
+
+// one line we found, with everything needed to say where it is
+// line numbers start at 1, byte_offset is where the line starts in the whole input
+// a --multiline match can cover more lines, last_line_number is the last of them
+// spans are the byte ranges of every match inside line, context lines have no spans
+// raw is the line as it was read, also without its line ending, line only differs from it
+// when the input was not valid UTF-8 (--json needs the bytes then)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record<'a> {
+    pub line_number: u64,
+    pub last_line_number: u64,
+    pub byte_offset: u64,
+    pub line: &'a str,
+    pub raw: &'a [u8],
+    pub spans: Vec<Range<usize>>,
+}
+
+// everything the caller needs to print, in the order it should be printed
+#[derive(Debug, PartialEq)]
+pub enum Event<'a> {
+    Match(Record<'a>),
+    Context(Record<'a>),
+    // the -- line between two groups that are not next to each other
+    Break,
+}
+
+// a line kept around because it may become before context
+struct Buffered {
+    line_number: u64,
+    byte_offset: u64,
+    raw: Vec<u8>,
+}
+
+// searches a whole string and returns a record for every matching line, the lines borrow from contents
+pub fn search_str<'a>(matcher: &Matcher, contents: &'a str) -> Vec<Record<'a>> {
+    let mut records = Vec::new();
+    let mut byte_offset = 0;
+
+    for (i, raw) in contents.split_inclusive('\n').enumerate() {
+        let line = trim_line_ending(raw);
+        let spans = matcher.find_spans(line);
+        if !spans.is_empty() {
+            records.push(Record {
+                line_number: i as u64 + 1,
+                last_line_number: i as u64 + 1,
+                byte_offset,
+                line,
+                raw: line.as_bytes(),
+                spans,
+            });
+        }
+        byte_offset += raw.len() as u64;
+    }
+
+    records
+}
+
+// calls on_match for every matching line as soon as it is found and returns how many lines matched
+// the line in the record has its line ending removed, same as str::lines()
+pub fn search_reader<R, F>(matcher: &Matcher, reader: R, mut on_match: F) -> io::Result<u64>
+where
+    R: BufRead,
+    F: FnMut(Record) -> io::Result<()>,
+{
+    search_reader_with_context(matcher, reader, Context::default(), |event| match event {
+        Event::Match(record) => on_match(record),
+        _ => Ok(()),
+    })
+}
+
+// same as search_reader but also sends the lines around every match
+// overlapping windows are merged so a line is never sent twice
+pub fn search_reader_with_context<R, F>(
+    matcher: &Matcher,
+    reader: R,
+    context: Context,
+    on_event: F,
+) -> io::Result<u64>
+where
+    R: BufRead,
+    F: FnMut(Event) -> io::Result<()>,
+{
+    let options = Options {
+        context,
+        ..Options::default()
+    };
+    search_reader_with(matcher, reader, &options, on_event)
+}
+
+// the search every other function is built on, returns how many lines were selected
+// with invert the selected lines are the ones without a match (and they have no spans)
+pub fn search_reader_with<R, F>(
+    matcher: &Matcher,
+    mut reader: R,
+    options: &Options,
+    mut on_event: F,
+) -> io::Result<u64>
+where
+    R: BufRead,
+    F: FnMut(Event) -> io::Result<()>,
+{
+    let mut selector = Selector::new(options);
+    // one buffer reused for every line instead of a new String per line
+    // we read bytes and not a String, a file that is not valid UTF-8 should not stop the search
+    let mut buf = Vec::new();
+
+    while !selector.is_done() {
+        buf.clear();
+        let read = reader.read_until(b'\n', &mut buf)?;
+        if read == 0 {
+            break; // end of input
+        }
+        selector.line(matcher, &buf, &mut on_event)?;
+    }
+
+    Ok(selector.count)
+}
+
+// --multiline: a match can cross line endings, so the whole input is read first and searched
+// in one piece, a match becomes one record with every line it touches (and matches that share
+// a line end up in the same record), the lines around it are still sent one by one
+pub fn search_multiline<R, F>(
+    matcher: &Matcher,
+    mut reader: R,
+    options: &Options,
+    mut on_event: F,
+) -> io::Result<u64>
+where
+    R: BufRead,
+    F: FnMut(Event) -> io::Result<()>,
+{
+    let mut buf = Vec::new();
+    reader.read_to_end(&mut buf)?;
+    let input = Lines::new(&buf);
+    let spans = match matcher {
+        // an empty query matches every line once, in the whole text that is at every line start
+        Matcher::Sensitive(query) | Matcher::Insensitive(query) if query.is_empty() => input.starts
+            [..input.count()]
+            .iter()
+            .map(|&(_, start)| start..start)
+            .collect(),
+        _ => matcher.find_spans(&input.text),
+    };
+    let mut spans = spans.into_iter().peekable();
+    let mut selector = Selector::new(options);
+
+    let mut first = 0;
+    while first < input.count() && !selector.is_done() {
+        // the matches that start on this line, they can make the record longer and then
+        // the ones starting on those lines belong to it too
+        let mut last = first;
+        let mut block = Vec::new();
+        while let Some(span) = spans.next_if(|span| input.line_of(span.start) <= last) {
+            last = last.max(input.line_of(span.end.max(span.start + 1) - 1));
+            block.push(span);
+        }
+
+        let (raw, text) = input.range(first, last);
+        let (trimmed_raw, line) = (trim_line_ending_bytes(raw), trim_line_ending(text));
+        let start = input.starts[first].1;
+        let spans = block
+            .into_iter()
+            .map(|span| {
+                // a match that ends with the line ending itself stops at the end of the line
+                (span.start - start).min(line.len())..(span.end - start).min(line.len())
+            })
+            .collect();
+        let lines = (last - first + 1) as u64;
+        selector.unit(raw.len(), lines, trimmed_raw, line, spans, &mut on_event)?;
+        first = last + 1;
+    }
+
+    Ok(selector.count)
+}
+
+// --query: NEAR/k looks k lines ahead and at file scope the last line can change everything,
+// so like search_multiline the whole input is read first
+// at file scope nothing is sent, the count is 1 when the file matches (and 0 otherwise)
+pub fn search_query<R, F>(
+    query: &QueryMatcher,
+    mut reader: R,
+    options: &Options,
+    mut on_event: F,
+) -> io::Result<u64>
+where
+    R: BufRead,
+    F: FnMut(Event) -> io::Result<()>,
+{
+    let mut buf = Vec::new();
+    reader.read_to_end(&mut buf)?;
+    let input = Lines::new(&buf);
+    let lines: Vec<&str> = (0..input.count())
+        .map(|i| trim_line_ending(input.range(i, i).1))
+        .collect();
+    if query.scope == Scope::File {
+        return Ok((query.file(&lines) != options.invert) as u64);
+    }
+
+    let mut selector = Selector::new(options);
+    for (i, selected) in query.select(&lines).into_iter().enumerate() {
+        if selector.is_done() {
+            break;
+        }
+        let (raw, line) = (input.range(i, i).0, lines[i]);
+        let spans = if selected {
+            query.find_spans(line)
+        } else {
+            Vec::new()
+        };
+        let trimmed = trim_line_ending_bytes(raw);
+        selector.unit_with(raw.len(), 1, trimmed, line, selected, spans, &mut on_event)?;
+    }
+
+    Ok(selector.count)
+}
+
+// the whole input of search_multiline as one text, with where every line starts
+struct Lines<'a> {
+    raw: &'a [u8],
+    // the same as raw unless it is not valid UTF-8, then every bad byte is a � and the
+    // text is longer than the bytes
+    text: Cow<'a, str>,
+    // (start in raw, start in text) of every line, and one more for the end of the input
+    starts: Vec<(usize, usize)>,
+}
+
+impl<'a> Lines<'a> {
+    fn new(raw: &'a [u8]) -> Lines<'a> {
+        let valid = std::str::from_utf8(raw).ok();
+        let mut lossy = String::new();
+        let mut starts = Vec::new();
+        let mut at = 0;
+        // a \n is never part of a longer char, so we can turn the lines into text one by one
+        for line in raw.split_inclusive(|&b| b == b'\n') {
+            starts.push((at, if valid.is_some() { at } else { lossy.len() }));
+            if valid.is_none() {
+                lossy.push_str(&String::from_utf8_lossy(line));
+            }
+            at += line.len();
+        }
+        let text = match valid {
+            Some(text) => Cow::Borrowed(text),
+            None => Cow::Owned(lossy),
+        };
+        starts.push((raw.len(), text.len()));
+        Lines { raw, text, starts }
+    }
+
+    fn count(&self) -> usize {
+        self.starts.len() - 1
+    }
+
+    // the line a byte of the text is on, the end of the text counts as the last line
+    fn line_of(&self, at: usize) -> usize {
+        let after = self.starts.partition_point(|&(_, start)| start <= at);
+        after.saturating_sub(1).min(self.count().saturating_sub(1))
+    }
+
+    // the bytes and the text of the lines first to last, with their line endings
+    fn range(&self, first: usize, last: usize) -> (&[u8], &str) {
+        let (start, end) = (self.starts[first], self.starts[last + 1]);
+        (&self.raw[start.0..end.0], &self.text[start.1..end.1])
+    }
+}
+
+// decides for one line after the other if it is sent as a match, as context or not at all
+// search_reader_with and the lazy iterators below feed it their lines
+struct Selector {
+    options: Options,
+    // how many lines were selected so far
+    count: u64,
+    // the last few lines we saw, in case the next line matches and needs them as before context
+    before: VecDeque<Buffered>,
+    // how many lines after the last match still have to be sent
+    after_left: usize,
+    line_number: u64,
+    byte_offset: u64,
+    // line number of the last line we sent, used to decide if we need a -- separator
+    last_sent: Option<u64>,
+}
+
+impl Selector {
+    fn new(options: &Options) -> Selector {
+        Selector {
+            options: *options,
+            count: 0,
+            before: VecDeque::with_capacity(options.context.before),
+            after_left: 0,
+            line_number: options.lines_before,
+            byte_offset: options.bytes_before,
+            last_sent: None,
+        }
+    }
+
+    // the after context of the last match is still sent, like grep -m does
+    fn is_done(&self) -> bool {
+        self.options.max_count.is_some_and(|max| self.count >= max) && self.after_left == 0
+    }
+
+    // buf is the next line with its line ending, the way read_until gives it to us
+    fn line<F>(&mut self, matcher: &Matcher, buf: &[u8], on_event: &mut F) -> io::Result<()>
+    where
+        F: FnMut(Event) -> io::Result<()>,
+    {
+        // valid UTF-8 is only borrowed, anything else (like a Latin-1 log) gets a � for every bad byte
+        let raw = trim_line_ending_bytes(buf);
+        let text = String::from_utf8_lossy(raw);
+        self.text_line(matcher, buf.len(), raw, &text, on_event)
+    }
+
+    // the same for a line that is text already, a &str does not have to be checked again
+    // len counts the line ending too, raw and line dont have it
+    fn text_line<F>(
+        &mut self,
+        matcher: &Matcher,
+        len: usize,
+        raw: &[u8],
+        line: &str,
+        on_event: &mut F,
+    ) -> io::Result<()>
+    where
+        F: FnMut(Event) -> io::Result<()>,
+    {
+        let spans = matcher.find_spans(line);
+        self.unit(len, 1, raw, line, spans, on_event)
+    }
+
+    // one or more lines that are selected (or not) together, a --multiline match
+    // is the only thing that covers more than one line
+    fn unit<F>(
+        &mut self,
+        len: usize,
+        lines: u64,
+        raw: &[u8],
+        line: &str,
+        spans: Vec<Range<usize>>,
+        on_event: &mut F,
+    ) -> io::Result<()>
+    where
+        F: FnMut(Event) -> io::Result<()>,
+    {
+        let matched = !spans.is_empty();
+        self.unit_with(len, lines, raw, line, matched, spans, on_event)
+    }
+
+    // the same when something else decided if the lines match, a --query can match a line
+    // without any span in it
+    #[allow(clippy::too_many_arguments)]
+    fn unit_with<F>(
+        &mut self,
+        len: usize,
+        lines: u64,
+        raw: &[u8],
+        line: &str,
+        matched: bool,
+        mut spans: Vec<Range<usize>>,
+        on_event: &mut F,
+    ) -> io::Result<()>
+    where
+        F: FnMut(Event) -> io::Result<()>,
+    {
+        let context = self.options.context;
+        let line_number = self.line_number + 1;
+        self.line_number += lines;
+        let line_offset = self.byte_offset;
+        self.byte_offset += len as u64;
+
+        // a unit that is all old lines was printed (or not) last time
+        let selected =
+            matched != self.options.invert && self.line_number > self.options.searched_lines;
+        if self.options.invert {
+            spans.clear(); // nothing to point at in a line that does not match
+        }
+        if selected {
+            self.count += 1;
+
+            // the before lines are numbered right before this line
+            // without any context there are no groups, so no separators either
+            let first = self
+                .before
+                .front()
+                .map_or(line_number, |old| old.line_number);
+            let with_context = context != Context::default();
+            if with_context && self.last_sent.is_some_and(|last| first > last + 1) {
+                on_event(Event::Break)?;
+            }
+            for old in self.before.drain(..) {
+                on_event(Event::Context(Record {
+                    line_number: old.line_number,
+                    last_line_number: old.line_number,
+                    byte_offset: old.byte_offset,
+                    line: &String::from_utf8_lossy(&old.raw),
+                    raw: &old.raw,
+                    spans: Vec::new(),
+                }))?;
+            }
+            on_event(Event::Match(Record {
+                line_number,
+                last_line_number: self.line_number,
+                byte_offset: line_offset,
+                line,
+                raw,
+                spans,
+            }))?;
+
+            self.last_sent = Some(self.line_number);
+            self.after_left = context.after;
+        } else if self.after_left > 0 {
+            on_event(Event::Context(Record {
+                line_number,
+                last_line_number: self.line_number,
+                byte_offset: line_offset,
+                line,
+                raw,
+                spans,
+            }))?;
+            self.last_sent = Some(self.line_number);
+            self.after_left -= 1;
+        } else if context.before > 0 {
+            // reuse the buffer of the oldest line when the window is full
+            let mut old = if self.before.len() == context.before {
+                self.before
+                    .pop_front()
+                    .map(|old| old.raw)
+                    .unwrap_or_default()
+            } else {
+                Vec::new()
+            };
+            old.clear();
+            old.extend_from_slice(raw);
+            self.before.push_back(Buffered {
+                line_number,
+                byte_offset: line_offset,
+                raw: old,
+            });
+        }
+        Ok(())
+    }
+}
+
+// one line the lazy iterators give out, a Record that knows if it matched
+// the -- between groups is left out, a gap in the line numbers shows the same
+#[derive(Debug, Clone, PartialEq)]
+pub struct Found<'a> {
+    // false for the lines of context around a match
+    pub is_match: bool,
+    pub line_number: u64,
+    pub last_line_number: u64,
+    pub byte_offset: u64,
+    // borrowed from the &str we search, a reader has to give us an owned copy of its buffer
+    pub line: Cow<'a, str>,
+    pub spans: Vec<Range<usize>>,
+}
+
+impl<'a> Found<'a> {
+    fn new(event: Event, line: impl FnOnce(&Record) -> Cow<'a, str>) -> Option<Found<'a>> {
+        let (is_match, record) = match event {
+            Event::Match(record) => (true, record),
+            Event::Context(record) => (false, record),
+            Event::Break => return None,
+        };
+        Some(Found {
+            is_match,
+            line_number: record.line_number,
+            last_line_number: record.last_line_number,
+            byte_offset: record.byte_offset,
+            line: line(&record),
+            spans: record.spans,
+        })
+    }
+}
+
+// a lazy search over a &str, every line is only looked at when the next match is asked for
+pub struct StrMatches<'m, 'a> {
+    matcher: &'m Matcher,
+    contents: &'a str,
+    lines: std::str::SplitInclusive<'a, char>,
+    selector: Selector,
+    // a match with its before context comes as more than one line at once
+    pending: VecDeque<Found<'a>>,
+}
+
+pub fn matches_in_str<'m, 'a>(
+    matcher: &'m Matcher,
+    contents: &'a str,
+    options: &Options,
+) -> StrMatches<'m, 'a> {
+    StrMatches {
+        matcher,
+        contents,
+        lines: contents.split_inclusive('\n'),
+        selector: Selector::new(options),
+        pending: VecDeque::new(),
+    }
+}
+
+impl<'a> Iterator for StrMatches<'_, 'a> {
+    type Item = Found<'a>;
+
+    fn next(&mut self) -> Option<Found<'a>> {
+        while self.pending.is_empty() && !self.selector.is_done() {
+            let line = self.lines.next()?;
+            let (contents, pending) = (self.contents, &mut self.pending);
+            // every line is a slice of contents, even the before context the selector copied
+            let borrow = |record: &Record| {
+                let start = record.byte_offset as usize;
+                Cow::Borrowed(&contents[start..start + record.raw.len()])
+            };
+            let text = trim_line_ending(line);
+            // only on_event can fail and ours never does
+            let _ = self.selector.text_line(
+                self.matcher,
+                line.len(),
+                text.as_bytes(),
+                text,
+                &mut |event| {
+                    pending.extend(Found::new(event, borrow));
+                    Ok(())
+                },
+            );
+        }
+        self.pending.pop_front()
+    }
+}
+
+// the same over a BufRead, reading can fail so every item is a Result
+pub struct Matches<'m, R> {
+    matcher: &'m Matcher,
+    reader: R,
+    selector: Selector,
+    buf: Vec<u8>,
+    pending: VecDeque<Found<'static>>,
+}
+
+pub fn matches_in_reader<'m, R: BufRead>(
+    matcher: &'m Matcher,
+    reader: R,
+    options: &Options,
+) -> Matches<'m, R> {
+    Matches {
+        matcher,
+        reader,
+        selector: Selector::new(options),
+        buf: Vec::new(),
+        pending: VecDeque::new(),
+    }
+}
+
+impl<R: BufRead> Iterator for Matches<'_, R> {
+    type Item = io::Result<Found<'static>>;
+
+    fn next(&mut self) -> Option<io::Result<Found<'static>>> {
+        while self.pending.is_empty() && !self.selector.is_done() {
+            self.buf.clear();
+            match self.reader.read_until(b'\n', &mut self.buf) {
+                Ok(0) => return None, // end of input
+                Ok(_) => {}
+                Err(e) => return Some(Err(e)),
+            }
+            let pending = &mut self.pending;
+            // only on_event can fail and ours never does
+            let _ = self.selector.line(self.matcher, &self.buf, &mut |event| {
+                pending.extend(Found::new(event, |record| {
+                    Cow::Owned(record.line.to_string())
+                }));
+                Ok(())
+            });
+        }
+        self.pending.pop_front().map(Ok)
+    }
+}
+
+// a NUL byte in the first block is how grep spots binary files, text files never have one
+// this only looks at what the reader already buffered, nothing is consumed
+pub fn is_binary<R: BufRead>(reader: &mut R) -> io::Result<bool> {
+    Ok(memchr::memchr(0, reader.fill_buf()?).is_some())
+}
+
+fn trim_line_ending(line: &str) -> &str {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+// the same for a line we have not turned into text yet
+fn trim_line_ending_bytes(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search_sensitive;
+
+    #[test]
+    fn streams_same_lines_as_search_sensitive() {
+        let contents = "\
+Rust:
+safe, fast, productive.\r
+Pick three.
+no newline at the end, productive";
+
+        let matcher = Matcher::Sensitive("duct".to_string());
+        let mut found = Vec::new();
+        let count = search_reader(&matcher, contents.as_bytes(), |record| {
+            found.push(record.line.to_string());
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(2, count);
+        assert_eq!(search_sensitive("duct", contents), found);
+    }
+
+    fn events(contents: &str, query: &str, context: Context) -> Vec<String> {
+        let matcher = Matcher::Insensitive(crate::fold::fold(query));
+        let mut events = Vec::new();
+        search_reader_with_context(&matcher, contents.as_bytes(), context, |event| {
+            events.push(match event {
+                Event::Match(record) => format!(":{}", record.line),
+                Event::Context(record) => format!("-{}", record.line),
+                Event::Break => "--".to_string(),
+            });
+            Ok(())
+        })
+        .unwrap();
+        events
+    }
+
+    #[test]
+    fn context_groups_and_separators() {
+        let contents = "1\n2\nhit 3\n4\n5\n6\n7\nHIT 8\n9";
+
+        assert_eq!(
+            vec!["-2", ":hit 3", "-4", "--", "-7", ":HIT 8", "-9"],
+            events(
+                contents,
+                "hit",
+                Context {
+                    before: 1,
+                    after: 1
+                }
+            )
+        );
+        assert_eq!(
+            vec![":hit 3", "-4", "-5", "--", ":HIT 8", "-9"],
+            events(
+                contents,
+                "hit",
+                Context {
+                    before: 0,
+                    after: 2
+                }
+            )
+        );
+        // no -- between matches that are not next to each other when no context was asked for
+        assert_eq!(
+            vec![":hit 3", ":HIT 8"],
+            events(contents, "hit", Context::default())
+        );
+    }
+
+    #[test]
+    fn overlapping_context_is_merged() {
+        let contents = "1\nhit 2\n3\nhit 4\n5\n6";
+
+        // the windows of both matches overlap so there is no separator and line 3 is sent once
+        assert_eq!(
+            vec!["-1", ":hit 2", "-3", ":hit 4", "-5", "-6"],
+            events(
+                contents,
+                "hit",
+                Context {
+                    before: 2,
+                    after: 2
+                }
+            )
+        );
+
+        // adjacent windows are merged as well
+        assert_eq!(
+            vec![":hit 2", "-3", ":hit 4", "-5"],
+            events(
+                contents,
+                "hit",
+                Context {
+                    before: 0,
+                    after: 1
+                }
+            )
+        );
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)] // a line with one match really has a Vec of one span
+    fn records_know_where_they_are() {
+        let contents = "Rust:\r\nsafe, fast, productive.\nTrust me, rust.";
+        let matcher = Matcher::Insensitive("rust".to_string());
+
+        let records = search_str(&matcher, contents);
+        assert_eq!(
+            vec![
+                Record {
+                    line_number: 1,
+                    last_line_number: 1,
+                    byte_offset: 0,
+                    line: "Rust:",
+                    raw: b"Rust:",
+                    spans: vec![0..4],
+                },
+                Record {
+                    line_number: 3,
+                    last_line_number: 3,
+                    byte_offset: 31,
+                    line: "Trust me, rust.",
+                    raw: b"Trust me, rust.",
+                    spans: vec![1..5, 10..14],
+                },
+            ],
+            records
+        );
+
+        // the streaming search finds exactly the same records
+        let mut streamed = Vec::new();
+        search_reader(&matcher, contents.as_bytes(), |record| {
+            streamed.push((record.line_number, record.byte_offset, record.spans));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(
+            vec![(1, 0, vec![0..4]), (3, 31, vec![1..5, 10..14])],
+            streamed
+        );
+    }
+
+    #[test]
+    fn invert_and_max_count() {
+        let contents = "Rust:\nsafe, fast, productive.\nPick three.\nTrust me.";
+        let matcher = Matcher::Insensitive("rust".to_string());
+
+        let mut lines = Vec::new();
+        let options = Options {
+            invert: true,
+            ..Options::default()
+        };
+        let count = search_reader_with(&matcher, contents.as_bytes(), &options, |event| {
+            if let Event::Match(record) = event {
+                assert!(record.spans.is_empty());
+                lines.push(record.line.to_string());
+            }
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(2, count);
+        assert_eq!(vec!["safe, fast, productive.", "Pick three."], lines);
+
+        // stops after the first match but still sends its after context
+        let mut events = 0;
+        let options = Options {
+            context: Context {
+                before: 0,
+                after: 1,
+            },
+            max_count: Some(1),
+            ..Options::default()
+        };
+        let count = search_reader_with(&matcher, contents.as_bytes(), &options, |_| {
+            events += 1;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!((1, 2), (count, events));
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn invalid_utf8_is_searched_lossy() {
+        // Latin-1 é is the single byte E9, not valid UTF-8
+        let contents: &[u8] = b"caf\xe9 au lait\nthe\x00 end\n";
+        let matcher = Matcher::Sensitive("lait".to_string());
+        let mut found = Vec::new();
+        let count = search_reader(&matcher, contents, |record| {
+            found.push((record.line.to_string(), record.spans));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(1, count);
+        assert_eq!(
+            vec![("caf\u{FFFD} au lait".to_string(), vec![10..14])],
+            found
+        );
+
+        assert!(!is_binary(&mut &contents[..10]).unwrap());
+        assert!(is_binary(&mut &contents[..]).unwrap());
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn multiline_matches_cross_lines() {
+        let contents = "fn add(\n    a: i32,\n) -> i32 {\nfn sub(a: i32) -> i32 {\nlet x = 1;\n";
+        let matcher = Matcher::Regex(regex::Regex::new(r"(?m)^fn \w+\(\s*a: i32").unwrap());
+        let options = Options {
+            context: Context {
+                before: 0,
+                after: 1,
+            },
+            ..Options::default()
+        };
+        let mut events = Vec::new();
+        let count = search_multiline(&matcher, contents.as_bytes(), &options, |event| {
+            events.push(match event {
+                Event::Match(r) => format!("{}:{}:{:?}", r.line_number, r.line, r.spans),
+                Event::Context(r) => format!("{}-{}", r.line_number, r.line),
+                Event::Break => "--".to_string(),
+            });
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(2, count);
+        assert_eq!(
+            vec![
+                "1:fn add(\n    a: i32,:[0..18]",
+                "3-) -> i32 {",
+                "4:fn sub(a: i32) -> i32 {:[0..13]",
+                "5-let x = 1;",
+            ],
+            events
+        );
+
+        // offsets are in bytes even when the text got a � for a bad byte
+        let contents: &[u8] = b"caf\xe9\nau\nlait\n";
+        let matcher = Matcher::Regex(regex::Regex::new(r"au\nlait").unwrap());
+        let mut found = Vec::new();
+        search_multiline(&matcher, contents, &Options::default(), |event| {
+            if let Event::Match(r) = event {
+                found.push((r.line_number, r.byte_offset, r.raw.to_vec(), r.spans));
+            }
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(vec![(2, 5, b"au\nlait".to_vec(), vec![0..7])], found);
+
+        // zero-length matches select every line they are on, the same as without -U
+        let contents = "a foo\nb\n\nc";
+        for matcher in [
+            Matcher::Sensitive(String::new()),
+            Matcher::Insensitive(String::new()),
+            Matcher::Regex(regex::Regex::new(r"x*").unwrap()),
+        ] {
+            let mut lines = Vec::new();
+            let count = search_multiline(
+                &matcher,
+                contents.as_bytes(),
+                &Options::default(),
+                |event| {
+                    if let Event::Match(r) = event {
+                        lines.push((r.line_number, r.line.to_string()));
+                    }
+                    Ok(())
+                },
+            )
+            .unwrap();
+            assert_eq!(4, count);
+            assert_eq!(
+                vec![1, 2, 3, 4],
+                lines.iter().map(|l| l.0).collect::<Vec<_>>()
+            );
+        }
+    }
+}
@@ -0,0 +1,226 @@
+// minigrep as a library: build a Searcher once, then search as many strings, readers or files
+// with it as you like, the matches come one at a time from an iterator
+//
+//     let searcher = Searcher::builder("frog").case(CaseMode::Smart).context(1, 1).build()?;
+//     for found in searcher.search_str(poem) {
+//         println!("{}: {}", found.line_number, found.line);
+//     }
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use crate::encoding::{self, Decoded};
+use crate::search::{self, Context, Matches, Options, StrMatches};
+use crate::{args, Config, Matcher, MinigrepError};
+
+// how upper and lower case are compared, the same as -s, -i and -S
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum CaseMode {
+    #[default]
+    Sensitive,
+    Insensitive,
+    // insensitive unless a pattern has an uppercase letter in it
+    Smart,
+}
+
+pub struct SearcherBuilder {
+    patterns: Vec<String>,
+    case: CaseMode,
+    regex: bool,
+    word: bool,
+    fuzzy: Option<usize>,
+    options: Options,
+}
+
+impl SearcherBuilder {
+    pub fn new(pattern: &str) -> SearcherBuilder {
+        SearcherBuilder {
+            patterns: vec![pattern.to_string()],
+            case: CaseMode::default(),
+            regex: false,
+            word: false,
+            fuzzy: None,
+            options: Options::default(),
+        }
+    }
+
+    // one more pattern, a line matches when any of them matches (like -e more than once)
+    pub fn pattern(mut self, pattern: &str) -> SearcherBuilder {
+        self.patterns.push(pattern.to_string());
+        self
+    }
+
+    pub fn case(mut self, case: CaseMode) -> SearcherBuilder {
+        self.case = case;
+        self
+    }
+
+    // the patterns are regular expressions instead of plain strings
+    pub fn regex(mut self, regex: bool) -> SearcherBuilder {
+        self.regex = regex;
+        self
+    }
+
+    // a match has to be a whole word, like -w
+    pub fn word(mut self, word: bool) -> SearcherBuilder {
+        self.word = word;
+        self
+    }
+
+    // substrings at most max_distance edits away from the pattern match too, like --fuzzy
+    pub fn fuzzy(mut self, max_distance: usize) -> SearcherBuilder {
+        self.fuzzy = Some(max_distance);
+        self
+    }
+
+    // stop after this many matching lines
+    pub fn max_count(mut self, max: u64) -> SearcherBuilder {
+        self.options.max_count = Some(max);
+        self
+    }
+
+    // lines before and after every match, like -B and -A
+    pub fn context(mut self, before: usize, after: usize) -> SearcherBuilder {
+        self.options.context = Context { before, after };
+        self
+    }
+
+    // fails for a regex that does not compile, or fuzzy with more than one plain pattern
+    pub fn build(self) -> Result<Searcher, MinigrepError> {
+        if self.fuzzy.is_some() && (self.patterns.len() != 1 || self.regex || self.word) {
+            return Err(MinigrepError::BadArgs(
+                "fuzzy needs exactly one pattern and can not be a regex or a word".to_string(),
+            ));
+        }
+        let case_sensitive = match self.case {
+            CaseMode::Sensitive => true,
+            CaseMode::Insensitive => false,
+            CaseMode::Smart => self
+                .patterns
+                .iter()
+                .any(|pattern| args::has_uppercase(pattern, self.regex)),
+        };
+        // the Matcher is made from a Config, the same way the command line does it
+        let config = Config {
+            patterns: self.patterns,
+            case_sensitive,
+            regex: self.regex,
+            word_regexp: self.word,
+            fuzzy: self.fuzzy,
+            ..Config::default()
+        };
+        Ok(Searcher {
+            matcher: Matcher::new(&config)?,
+            options: self.options,
+        })
+    }
+}
+
+pub struct Searcher {
+    matcher: Matcher,
+    options: Options,
+}
+
+impl Searcher {
+    pub fn builder(pattern: &str) -> SearcherBuilder {
+        SearcherBuilder::new(pattern)
+    }
+
+    // the lines in the results borrow from contents, nothing is copied
+    pub fn search_str<'s, 'a>(&'s self, contents: &'a str) -> StrMatches<'s, 'a> {
+        search::matches_in_str(&self.matcher, contents, &self.options)
+    }
+
+    pub fn search_reader<R: BufRead>(&self, reader: R) -> Matches<'_, R> {
+        search::matches_in_reader(&self.matcher, reader, &self.options)
+    }
+
+    // a file with a UTF-16 BOM is transcoded, the same as on the command line
+    pub fn search_path(
+        &self,
+        path: &Path,
+    ) -> Result<Matches<'_, Decoded<BufReader<File>>>, MinigrepError> {
+        let name = path.display().to_string();
+        let reader = File::open(path)
+            .and_then(|file| encoding::decode(BufReader::new(file), None))
+            .map_err(|e| MinigrepError::io(&name, e))?;
+        Ok(self.search_reader(reader))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::Found;
+    use std::io::Read;
+
+    fn lines(found: impl Iterator<Item = Found<'static>>) -> Vec<String> {
+        found
+            .map(|found| {
+                let sep = if found.is_match { ':' } else { '-' };
+                format!("{}{}{}", found.line_number, sep, found.line)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn builder_options() {
+        let poem = "I'm nobody! Who are you?\nAre you nobody, too?\nThen there's a pair of us\nDon't tell!";
+
+        let searcher = Searcher::builder("you").build().unwrap();
+        let found: Vec<Found> = searcher.search_str(poem).collect();
+        assert_eq!(2, found.len());
+        assert_eq!(
+            (2, 25, "Are you nobody, too?"),
+            (found[1].line_number, found[1].byte_offset, &*found[1].line)
+        );
+
+        // pair would match line 3, but max_count stops after the first match and its context
+        let searcher = Searcher::builder("who")
+            .pattern("pair")
+            .case(CaseMode::Smart)
+            .context(0, 1)
+            .max_count(1)
+            .build()
+            .unwrap();
+        assert_eq!(
+            vec!["1:I'm nobody! Who are you?", "2-Are you nobody, too?"],
+            lines(searcher.search_reader(poem.as_bytes()).map(Result::unwrap))
+        );
+
+        // with an uppercase letter smart case is case sensitive, and the word has to stand alone
+        let searcher = Searcher::builder("Are")
+            .pattern("Do")
+            .case(CaseMode::Smart)
+            .word(true)
+            .build()
+            .unwrap();
+        assert_eq!(
+            vec!["2:Are you nobody, too?"],
+            lines(searcher.search_reader(poem.as_bytes()).map(Result::unwrap))
+        );
+
+        assert!(Searcher::builder("(").regex(true).build().is_err());
+        assert!(Searcher::builder("a").word(true).fuzzy(1).build().is_err());
+    }
+
+    #[test]
+    fn iterators_are_lazy() {
+        // a reader that fails after the first line, the iterator never gets there
+        let reader = "hit\n".as_bytes().chain(FailingReader);
+        let searcher = Searcher::builder("hit").build().unwrap();
+        let mut matches = searcher.search_reader(BufReader::new(reader));
+        assert_eq!("hit", matches.next().unwrap().unwrap().line);
+        assert!(matches.next().unwrap().is_err());
+
+        assert!(searcher.search_path(Path::new("missing.txt")).is_err());
+    }
+
+    struct FailingReader;
+
+    impl Read for FailingReader {
+        fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("broken"))
+        }
+    }
+}
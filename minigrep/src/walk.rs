@@ -0,0 +1,358 @@
+// walking a directory tree to find every file we need to search
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::ignore::{FileFilter, IgnoreRules};
+
+// hidden files and folders start with a dot on unix, like .git or .gitignore
+pub fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| name.starts_with('.'))
+        .unwrap_or(false)
+}
+
+// which files a directory walk should find
+#[derive(Default)]
+pub struct WalkOptions {
+    // also walk into hidden files and folders (--hidden)
+    pub hidden: bool,
+    // skip what .gitignore and .ignore files say, turned off with --no-ignore
+    pub ignore: bool,
+    // --glob and --type
+    pub filter: FileFilter,
+}
+
+// what a walk found, sorted by name inside every directory so the output is always in the same order
+// errors are the directories and entries below root that could not be read, one of those
+// should not stop the whole search, the caller reports them and counts them as failed
+#[derive(Debug, Default)]
+pub struct Walk {
+    pub files: Vec<PathBuf>,
+    pub errors: Vec<(PathBuf, io::Error)>,
+}
+
+// returns all files below root, only root itself not being readable is an error
+// symlinked directories are not followed, this way we can never end up in a loop
+pub fn walk(root: &Path, include_hidden: bool) -> io::Result<Walk> {
+    let options = WalkOptions {
+        hidden: include_hidden,
+        ..WalkOptions::default()
+    };
+    walk_with(root, &options)
+}
+
+// the same as walk but with ignore files and filters
+pub fn walk_with(root: &Path, options: &WalkOptions) -> io::Result<Walk> {
+    let mut walk = Walk::default();
+    let above = if options.ignore {
+        ignores_above(root)
+    } else {
+        Vec::new()
+    };
+    let mut walker = Walker {
+        options,
+        ignores: Vec::new(),
+        above,
+        walk: &mut walk,
+    };
+    walker.walk_dir(root, "")?;
+    Ok(walk)
+}
+
+struct Walker<'a> {
+    options: &'a WalkOptions,
+    // the ignore rules of every directory from root down to the current one,
+    // with the path of that directory relative to root
+    ignores: Vec<(String, IgnoreRules)>,
+    // the ignore rules of the directories above root, the closest first, with the path of
+    // root relative to that directory
+    above: Vec<(String, IgnoreRules)>,
+    walk: &'a mut Walk,
+}
+
+// `minigrep foo src` has to skip the same files as `minigrep foo .` at the top of the repository,
+// so the ignore files between root and the directory with the .git in it count too
+// outside of a repository there is nothing above root to look at
+fn ignores_above(root: &Path) -> Vec<(String, IgnoreRules)> {
+    let Ok(mut dir) = fs::canonicalize(root) else {
+        return Vec::new();
+    };
+    let mut above = Vec::new();
+    let mut rel = String::new();
+    while !dir.join(".git").exists() {
+        let (Some(parent), Some(name)) = (dir.parent(), dir.file_name()) else {
+            return Vec::new(); // we got to / without finding a repository
+        };
+        let name = name.to_string_lossy();
+        rel = if rel.is_empty() {
+            name.into_owned()
+        } else {
+            format!("{}/{}", name, rel)
+        };
+        if let Some(rules) = IgnoreRules::from_dir(parent) {
+            above.push((rel.clone(), rules));
+        }
+        dir = parent.to_path_buf();
+    }
+    above
+}
+
+impl Walker<'_> {
+    // rel is the path of dir relative to root, "" for root itself
+    fn walk_dir(&mut self, dir: &Path, rel: &str) -> io::Result<()> {
+        let rules = if self.options.ignore {
+            IgnoreRules::from_dir(dir)
+        } else {
+            None
+        };
+        let pushed = rules.is_some();
+        if let Some(rules) = rules {
+            self.ignores.push((rel.to_string(), rules));
+        }
+
+        let result = self.walk_entries(dir, rel);
+        if pushed {
+            self.ignores.pop();
+        }
+        result
+    }
+
+    fn walk_entries(&mut self, dir: &Path, rel: &str) -> io::Result<()> {
+        // read_dir returns the entries in whatever order the filesystem likes so we sort them ourselves
+        let mut entries = Vec::new();
+        let read = fs::read_dir(dir).map(|found| {
+            for entry in found {
+                match entry {
+                    Ok(entry) => entries.push(entry),
+                    Err(e) => self.walk.errors.push((dir.to_path_buf(), e)),
+                }
+            }
+        });
+        match read {
+            Ok(()) => {}
+            // the caller says what happened to the directory it asked for
+            Err(e) if rel.is_empty() => return Err(e),
+            // a directory we can not read below that is skipped, its siblings are still searched
+            Err(e) => {
+                self.walk.errors.push((dir.to_path_buf(), e));
+                return Ok(());
+            }
+        }
+        entries.sort_by_key(|entry| entry.file_name());
+
+        for entry in entries {
+            let path = entry.path();
+            if !self.options.hidden && is_hidden(&path) {
+                continue;
+            }
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let entry_rel = if rel.is_empty() {
+                name
+            } else {
+                format!("{}/{}", rel, name)
+            };
+
+            // does not follow symlinks
+            let file_type = match entry.file_type() {
+                Ok(file_type) => file_type,
+                Err(e) => {
+                    self.walk.errors.push((path, e));
+                    continue;
+                }
+            };
+            let is_dir = file_type.is_dir();
+            let is_file = file_type.is_file() || (file_type.is_symlink() && path.is_file());
+            if !is_dir && !is_file {
+                continue; // sockets, fifos and broken links
+            }
+            if self.is_ignored(&entry_rel, is_dir) {
+                continue;
+            }
+            if is_dir {
+                if !self.options.filter.is_dir_excluded(&entry_rel) {
+                    self.walk_dir(&path, &entry_rel)?;
+                }
+            } else if self.options.filter.is_file_selected(&entry_rel) {
+                self.walk.files.push(path);
+            }
+        }
+
+        Ok(())
+    }
+
+    // the closest ignore file that has a rule for the path decides
+    fn is_ignored(&self, rel: &str, is_dir: bool) -> bool {
+        for (dir, rules) in self.ignores.iter().rev() {
+            let inside = if dir.is_empty() {
+                rel
+            } else {
+                &rel[dir.len() + 1..]
+            };
+            if let Some(ignored) = rules.matched(inside, is_dir) {
+                return ignored;
+            }
+        }
+        for (root, rules) in &self.above {
+            if let Some(ignored) = rules.matched(&format!("{}/{}", root, rel), is_dir) {
+                return ignored;
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn walks_sorted_and_skips_hidden() {
+        let root = std::env::temp_dir().join(format!("minigrep_walk_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("b/inner")).unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::write(root.join("c.txt"), "c").unwrap();
+        fs::write(root.join("a.txt"), "a").unwrap();
+        fs::write(root.join("b/inner/d.txt"), "d").unwrap();
+        fs::write(root.join(".hidden"), "h").unwrap();
+        fs::write(root.join(".git/config"), "g").unwrap();
+
+        let files = walk(&root, false).unwrap().files;
+        let names: Vec<_> = files
+            .iter()
+            .map(|f| f.strip_prefix(&root).unwrap())
+            .collect();
+        assert_eq!(
+            vec![
+                Path::new("a.txt"),
+                Path::new("b/inner/d.txt"),
+                Path::new("c.txt")
+            ],
+            names
+        );
+
+        let files = walk(&root, true).unwrap().files;
+        assert_eq!(5, files.len()); // .hidden and .git/config are included now
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn honors_ignore_files_and_filters() {
+        let root = std::env::temp_dir().join(format!("minigrep_ignore_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("src/gen")).unwrap();
+        fs::create_dir_all(root.join("target")).unwrap();
+        fs::write(root.join(".gitignore"), "target/\n*.log\n").unwrap();
+        fs::write(root.join("src/.ignore"), "gen/\n!keep.log\n").unwrap();
+        fs::write(root.join("src/main.rs"), "m").unwrap();
+        fs::write(root.join("src/keep.log"), "k").unwrap();
+        fs::write(root.join("src/gen/out.rs"), "o").unwrap();
+        fs::write(root.join("target/app.rs"), "t").unwrap();
+        fs::write(root.join("run.log"), "r").unwrap();
+        fs::write(root.join("notes.md"), "n").unwrap();
+
+        let names = |options: &WalkOptions| -> Vec<String> {
+            walk_with(&root, options)
+                .unwrap()
+                .files
+                .iter()
+                .map(|f| f.strip_prefix(&root).unwrap().display().to_string())
+                .collect()
+        };
+        let options = WalkOptions {
+            ignore: true,
+            ..WalkOptions::default()
+        };
+        assert_eq!(
+            vec!["notes.md", "src/keep.log", "src/main.rs"],
+            names(&options)
+        );
+
+        let options = WalkOptions {
+            ignore: false,
+            ..WalkOptions::default()
+        };
+        assert_eq!(6, names(&options).len());
+
+        let options = WalkOptions {
+            ignore: false,
+            filter: FileFilter::new(&[], &["rust".to_string()], &[]).unwrap(),
+            ..WalkOptions::default()
+        };
+        assert_eq!(
+            vec!["src/gen/out.rs", "src/main.rs", "target/app.rs"],
+            names(&options)
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn ignore_files_above_root_count_inside_a_repository() {
+        let root = std::env::temp_dir().join(format!("minigrep_above_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("repo/.git")).unwrap();
+        fs::create_dir_all(root.join("repo/src/gen")).unwrap();
+        fs::write(root.join(".gitignore"), "*.rs\n").unwrap(); // not in the repository
+        fs::write(root.join("repo/.gitignore"), "*.log\n/src/gen/\n").unwrap();
+        fs::write(root.join("repo/src/.ignore"), "!keep.log\n").unwrap();
+        fs::write(root.join("repo/src/main.rs"), "m").unwrap();
+        fs::write(root.join("repo/src/run.log"), "r").unwrap();
+        fs::write(root.join("repo/src/keep.log"), "k").unwrap();
+        fs::write(root.join("repo/src/gen/out.rs"), "o").unwrap();
+
+        let options = WalkOptions {
+            ignore: true,
+            ..WalkOptions::default()
+        };
+        let names = |dir: &Path| -> Vec<String> {
+            walk_with(dir, &options)
+                .unwrap()
+                .files
+                .iter()
+                .map(|f| f.strip_prefix(dir).unwrap().display().to_string())
+                .collect()
+        };
+        assert_eq!(vec!["keep.log", "main.rs"], names(&root.join("repo/src")));
+        assert_eq!(
+            vec!["src/keep.log", "src/main.rs"],
+            names(&root.join("repo"))
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn keeps_going_past_unreadable_dirs() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = std::env::temp_dir().join(format!("minigrep_locked_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("b_locked")).unwrap();
+        fs::write(root.join("a.txt"), "a").unwrap();
+        fs::write(root.join("b_locked/secret.txt"), "s").unwrap();
+        fs::write(root.join("c.txt"), "c").unwrap();
+        let locked = root.join("b_locked");
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+
+        let walk = walk(&root, false).unwrap();
+        // root can read it anyway, then there is nothing to test
+        if fs::read_dir(&locked).is_err() {
+            let names: Vec<_> = walk
+                .files
+                .iter()
+                .map(|f| f.strip_prefix(&root).unwrap())
+                .collect();
+            assert_eq!(vec![Path::new("a.txt"), Path::new("c.txt")], names);
+            assert_eq!(1, walk.errors.len());
+            assert_eq!(locked, walk.errors[0].0);
+        }
+
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+        assert!(super::walk(&root, false).is_err());
+    }
+}
@@ -0,0 +1,422 @@
+// --watch: search once, then keep looking at the files and search again when they change
+// we poll the size and mtime of every file instead of asking the OS to tell us (inotify, FSEvents,
+// ...), that is a bit slower to notice a change but works everywhere, network drives included
+// a file that only grew, with everything we searched before still in place, was appended to
+// like a log: only its new complete lines are printed, the way tail -f shows them, but the
+// search starts a few lines earlier so -B, -U and NEAR/k still see the lines before them
+// anything else (it got shorter, or the end of what we searched changed) was rewritten, then
+// all of its matches are printed again
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File};
+use std::io::{self, BufRead, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::encoding::{Encoding, Utf8Len};
+
+// how long we sleep between two looks at the files
+pub const INTERVAL: Duration = Duration::from_millis(250);
+// how many bytes before the end of what we searched are kept, to notice a rewrite
+const SAMPLE: usize = 4096;
+
+// what happened to a file since we last searched it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Change {
+    // a file we dont know yet, or one that was gone for a while
+    New,
+    // there are new bytes after the lines we searched already
+    Appended,
+    Rewritten,
+    Unchanged,
+}
+
+// size and mtime of a file when we looked at it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stamp {
+    pub size: u64,
+    mtime: Option<SystemTime>,
+}
+
+// what has to be searched after an append, see Watcher::appended
+#[derive(Debug, PartialEq)]
+pub struct Chunk {
+    // whole lines, the last few old ones and then the new ones, in the encoding of the file
+    // (without its BOM, see encoding::transcode)
+    pub data: Vec<u8>,
+    pub encoding: Encoding,
+    // how many lines and bytes of the file come before data, the bytes as -b counts them
+    pub lines_before: u64,
+    pub bytes_before: u64,
+    // the lines up to this line number were searched already, for search::Options
+    pub searched_lines: u64,
+}
+
+// what we know about the lines of a file, filled in while a search reads it
+struct Progress {
+    // how many of the last lines to keep the start of, None for all of them (we start over
+    // at the beginning of the file then)
+    keep: Option<usize>,
+    encoding: Encoding,
+    bom: u64,
+    // bytes read so far
+    at: u64,
+    // everything before this was searched, it is always right after a \n (or the BOM)
+    // a last line without its \n yet is searched again once it is complete
+    bytes: u64,
+    lines: u64,
+    // the same as `bytes` but counted in the UTF-8 the search sees, what -b prints
+    text: u64,
+    utf8: Utf8Len,
+    // where the last `keep` complete lines start, in the file and in the UTF-8
+    starts: VecDeque<(u64, u64)>,
+    // at least the last SAMPLE bytes before `bytes`, and of the unfinished line after it
+    sample: Vec<u8>,
+    partial: Vec<u8>,
+}
+
+impl Progress {
+    fn new(keep: Option<usize>, encoding: Encoding, bom: usize) -> Progress {
+        Progress {
+            keep,
+            encoding,
+            bom: bom as u64,
+            at: 0,
+            bytes: bom as u64,
+            lines: 0,
+            text: 0,
+            utf8: Utf8Len::new(encoding, 0),
+            starts: VecDeque::new(),
+            sample: Vec::new(),
+            partial: Vec::new(),
+        }
+    }
+
+    fn feed(&mut self, data: &[u8]) {
+        let mut line_start = 0;
+        for i in self.line_ends(data) {
+            self.count(data, line_start, i + 1);
+            self.sample.append(&mut self.partial);
+            self.sample.extend_from_slice(&data[line_start..=i]);
+            keep_last(&mut self.sample);
+            if self.keep.is_some_and(|keep| keep > 0) {
+                self.starts.push_back((self.bytes, self.text));
+            }
+            if self.keep.is_some_and(|keep| self.starts.len() > keep) {
+                self.starts.pop_front();
+            }
+            self.bytes = self.at + i as u64 + 1;
+            self.text = self.utf8.len;
+            self.lines += 1;
+            line_start = i + 1;
+        }
+        self.count(data, line_start, data.len());
+        self.partial.extend_from_slice(&data[line_start..]);
+        keep_last(&mut self.partial);
+        self.at += data.len() as u64;
+    }
+
+    // where the lines in data end (the index of their last byte)
+    // in UTF-16 a \n is a whole unit, 0x0A with a 0x00 before or after it at an even offset
+    fn line_ends(&self, data: &[u8]) -> Vec<usize> {
+        let newline = match self.encoding {
+            Encoding::Utf8 | Encoding::Latin1 => return memchr::memchr_iter(b'\n', data).collect(),
+            Encoding::Utf16Be => [0x00, b'\n'],
+            Encoding::Utf16 | Encoding::Utf16Le => [b'\n', 0x00],
+        };
+        (0..data.len())
+            .filter(|&i| !(self.at + i as u64).is_multiple_of(2))
+            .filter(|&i| {
+                // the first byte of the unit can only be in the line we are in the middle of
+                let first = match i {
+                    0 => self.partial.last().copied(),
+                    _ => Some(data[i - 1]),
+                };
+                [first, Some(data[i])] == newline.map(Some)
+            })
+            .collect()
+    }
+
+    // the UTF-8 of data[from..to], the BOM is not part of it
+    fn count(&mut self, data: &[u8], from: usize, to: usize) {
+        let bom_end = self.bom.saturating_sub(self.at).min(to as u64) as usize;
+        self.utf8.count(&data[from.max(bom_end)..to]);
+    }
+
+    fn sample(&self) -> &[u8] {
+        &self.sample[self.sample.len().saturating_sub(SAMPLE)..]
+    }
+}
+
+// only the last SAMPLE bytes are needed, they are cut off once in a while and not for every line
+fn keep_last(bytes: &mut Vec<u8>) {
+    if bytes.len() > 2 * SAMPLE {
+        bytes.drain(..bytes.len() - SAMPLE);
+    }
+}
+
+// a reader that tells the watcher about every byte a search reads, so the file does not
+// have to be read a second time to know where its lines are
+pub struct Tracked<R> {
+    inner: R,
+    progress: Progress,
+}
+
+impl<R: BufRead> BufRead for Tracked<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        // the bytes are still in the buffer of inner, asking for them again reads nothing
+        if let Ok(buf) = self.inner.fill_buf() {
+            self.progress.feed(&buf[..amt.min(buf.len())]);
+        }
+        self.inner.consume(amt);
+    }
+}
+
+impl<R: BufRead> Read for Tracked<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+struct Seen {
+    stamp: Stamp,
+    progress: Progress,
+}
+
+pub struct Watcher {
+    seen: HashMap<PathBuf, Seen>,
+    // how many old lines are searched again with the new ones, None for the whole file
+    keep: Option<usize>,
+}
+
+impl Watcher {
+    pub fn new(keep: Option<usize>) -> Watcher {
+        Watcher {
+            seen: HashMap::new(),
+            keep,
+        }
+    }
+
+    // fails when the file is gone (or was never there)
+    pub fn check(&self, path: &Path) -> io::Result<(Change, Stamp)> {
+        let metadata = fs::metadata(path)?;
+        let stamp = Stamp {
+            size: metadata.len(),
+            mtime: metadata.modified().ok(),
+        };
+        let Some(seen) = self.seen.get(path) else {
+            return Ok((Change::New, stamp));
+        };
+        if stamp == seen.stamp {
+            return Ok((Change::Unchanged, stamp));
+        }
+        let bytes = seen.progress.bytes;
+        if stamp.size < bytes {
+            return Ok((Change::Rewritten, stamp));
+        }
+        // the end of what we searched has to be the same, then the file was only added to
+        let expected = seen.progress.sample();
+        let mut sample = vec![0; expected.len()];
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(bytes - sample.len() as u64))?;
+        if file.read_exact(&mut sample).is_err() || sample != expected {
+            return Ok((Change::Rewritten, stamp));
+        }
+        Ok((Change::Appended, stamp))
+    }
+
+    // wraps the reader a new or rewritten file is searched with, the search has to read it
+    // from the very start, BOM included (encoding::detect tells the encoding and the BOM)
+    pub fn track<R: BufRead>(&self, inner: R, encoding: Encoding, bom: usize) -> Tracked<R> {
+        Tracked {
+            inner,
+            progress: Progress::new(self.keep, encoding, bom),
+        }
+    }
+
+    // after the first stamp.size bytes of path were searched through tracked
+    pub fn searched_all<R: BufRead>(
+        &mut self,
+        path: &Path,
+        stamp: Stamp,
+        mut tracked: Tracked<R>,
+    ) -> io::Result<()> {
+        // what the search did not need, like the rest of a binary file after its first match
+        io::copy(&mut tracked, &mut io::sink())?;
+        let seen = Seen {
+            stamp,
+            progress: tracked.progress,
+        };
+        self.seen.insert(path.to_path_buf(), seen);
+        Ok(())
+    }
+
+    // the lines added to path since we last searched it (check said Appended), together with
+    // the old lines that are kept, None when no new line is complete yet
+    // the new lines count as searched from now on
+    pub fn appended(&mut self, path: &Path, stamp: Stamp) -> io::Result<Option<Chunk>> {
+        let Some(seen) = self.seen.get_mut(path) else {
+            return Ok(None);
+        };
+        let progress = &mut seen.progress;
+        let (old_bytes, old_lines) = (progress.bytes, progress.lines);
+        let (from, text_from, kept) = match self.keep {
+            Some(_) => {
+                let (from, text_from) = progress
+                    .starts
+                    .front()
+                    .copied()
+                    .unwrap_or((old_bytes, progress.text));
+                (from, text_from, progress.starts.len() as u64)
+            }
+            None => (progress.bom, 0, old_lines),
+        };
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(from))?;
+        let mut data = Vec::new();
+        file.take(stamp.size.saturating_sub(from))
+            .read_to_end(&mut data)?;
+
+        // the unfinished line is read again, now that there is more of it
+        progress.at = old_bytes;
+        progress.partial.clear();
+        progress.utf8 = Utf8Len::new(progress.encoding, progress.text);
+        progress.feed(&data[(old_bytes - from) as usize..]);
+        seen.stamp = stamp;
+        data.truncate((progress.bytes - from) as usize);
+        if progress.lines == old_lines {
+            return Ok(None);
+        }
+        Ok(Some(Chunk {
+            data,
+            encoding: progress.encoding,
+            lines_before: old_lines - kept,
+            bytes_before: text_from,
+            searched_lines: old_lines,
+        }))
+    }
+
+    // a file that is gone, if it comes back it is new
+    pub fn forget(&mut self, path: &Path) {
+        self.seen.remove(path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufReader, Write};
+
+    fn search_all(watcher: &mut Watcher, path: &Path) -> Change {
+        let (change, stamp) = watcher.check(path).unwrap();
+        let file = File::open(path).unwrap().take(stamp.size);
+        let mut reader = BufReader::with_capacity(4, file);
+        let (encoding, bom) = crate::encoding::detect(reader.fill_buf().unwrap(), None);
+        let tracked = watcher.track(reader, encoding, bom);
+        watcher.searched_all(path, stamp, tracked).unwrap();
+        change
+    }
+
+    #[test]
+    fn appends_and_rewrites() {
+        let dir = std::env::temp_dir().join(format!("minigrep_watch_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("build.log");
+        fs::write(&path, "one\ntwo\nthr").unwrap();
+
+        // one old line is searched again with the new ones, for -B 1
+        let mut watcher = Watcher::new(Some(1));
+        assert_eq!(Change::New, search_all(&mut watcher, &path));
+        assert_eq!(Change::Unchanged, watcher.check(&path).unwrap().0);
+
+        // the unfinished line is searched again once it is complete, and not before
+        let mut file = File::options().append(true).open(&path).unwrap();
+        file.write_all(b"ee\nfo").unwrap();
+        let (change, stamp) = watcher.check(&path).unwrap();
+        assert_eq!(Change::Appended, change);
+        let chunk = Chunk {
+            data: b"two\nthree\n".to_vec(),
+            encoding: Encoding::Utf8,
+            lines_before: 1,
+            bytes_before: 4,
+            searched_lines: 2,
+        };
+        assert_eq!(Some(chunk), watcher.appended(&path, stamp).unwrap());
+        file.write_all(b"ur").unwrap();
+        let (change, stamp) = watcher.check(&path).unwrap();
+        assert_eq!(Change::Appended, change);
+        assert_eq!(None, watcher.appended(&path, stamp).unwrap());
+
+        // same length but different lines, and shorter
+        // (an mtime of its own, a quick rewrite can get the same one on some file systems)
+        fs::write(&path, "ONE\nTWO\nTHREE\nfour").unwrap();
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH)
+            .unwrap();
+        assert_eq!(Change::Rewritten, watcher.check(&path).unwrap().0);
+        fs::write(&path, "one\n").unwrap();
+        assert_eq!(Change::Rewritten, search_all(&mut watcher, &path));
+
+        // -U and --query start over at the beginning of the file
+        let mut everything = Watcher::new(None);
+        search_all(&mut everything, &path);
+        file.write_all(b"two\n").unwrap();
+        let (_, stamp) = everything.check(&path).unwrap();
+        let chunk = everything.appended(&path, stamp).unwrap().unwrap();
+        assert_eq!(
+            (b"one\ntwo\n".to_vec(), 0, 1),
+            (chunk.data, chunk.lines_before, chunk.searched_lines)
+        );
+
+        // UTF-16 is split into lines at whole \n units, -b counts the UTF-8 they turn into
+        let utf16 =
+            |text: &str| -> Vec<u8> { text.encode_utf16().flat_map(u16::to_le_bytes).collect() };
+        fs::write(&path, [&[0xff, 0xfe][..], &utf16("ok\n")].concat()).unwrap();
+        search_all(&mut watcher, &path);
+        let mut file = File::options().append(true).open(&path).unwrap();
+        let mut append = |bytes: &[u8]| {
+            file.write_all(bytes).unwrap();
+            let (change, stamp) = watcher.check(&path).unwrap();
+            assert_eq!(Change::Appended, change);
+            watcher.appended(&path, stamp).unwrap()
+        };
+        let chunk = append(&utf16("é\nh")).unwrap();
+        assert_eq!(
+            (utf16("ok\né\n"), Encoding::Utf16Le, 0, 0, 1),
+            (
+                chunk.data,
+                chunk.encoding,
+                chunk.lines_before,
+                chunk.bytes_before,
+                chunk.searched_lines
+            )
+        );
+        // a 0x0A byte is not a line yet, and not every 0x0A byte is a \n (U+010A is Ċ)
+        assert_eq!(None, append(&[b'i', 0x00, b'\n']));
+        assert_eq!(None, append(&[0x01, b'\n']));
+        let chunk = append(&[0x00]).unwrap();
+        assert_eq!(
+            (utf16("é\nhiĊ\n"), 1, 3, 2),
+            (
+                chunk.data,
+                chunk.lines_before,
+                chunk.bytes_before,
+                chunk.searched_lines
+            )
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(watcher.check(&path).is_err());
+    }
+}
@@ -0,0 +1,92 @@
+// a corpus of case insensitive searches in many languages, checked against -i, -e and -E
+// every line is (query, line, should it match)
+use minigrep::{search_insensitive, Config, Matcher};
+
+const CORPUS: &[(&str, &str, bool)] = &[
+    // plain ASCII
+    ("rust", "Trust me.", true),
+    ("RUST", "rust", true),
+    ("safe", "Safe, fast, productive.", true),
+    ("duct", "Pick three.", false),
+    // German: ẞ and ß are the same letter, but a simple fold never turns ß into ss
+    ("straße", "STRAẞE", true),
+    ("STRAẞE", "die straße", true),
+    ("strasse", "Straße", false),
+    ("ß", "SS", false),
+    ("über", "ÜBERALL", true),
+    // Turkish: İ and ı have no simple fold, they only match themselves
+    ("istanbul", "İstanbul", false),
+    ("İstanbul", "İSTANBUL", true),
+    ("stanbul", "İstanbul", true),
+    ("ı", "I", false),
+    ("i", "I", true),
+    ("ıi", "ıI", true),
+    // Greek: final sigma, small sigma and capital sigma are all the same
+    ("οδυσσευς", "ΟΔΥΣΣΕΥΣ", true),
+    ("ΟΔΥΣΣΕΥΣ", "ὁ Ὀδυσσεύς", false), // accents still count
+    ("οδυσσευσ", "οδυσσευς", true),
+    ("ς", "Σ", true),
+    // Cyrillic and Armenian have upper and lower case too
+    ("москва", "МОСКВА", true),
+    ("ԵՐԵՎԱՆ", "երևան", false), // և is its own letter
+    ("երեվան", "ԵՐԵՎԱՆ", true),
+    // letters that fold into ASCII
+    ("kelvin", "\u{212A}elvin", true),
+    ("sun", "\u{17F}un", true),
+    // scripts without case are compared as they are
+    ("東京", "東京タワー", true),
+    ("タワー", "とうきょうタワー", true),
+    ("", "anything", true),
+];
+
+fn check(name: &str, matches: impl Fn(&str, &str) -> bool) {
+    for &(query, line, expected) in CORPUS {
+        assert_eq!(
+            expected,
+            matches(query, line),
+            "{}: {:?} in {:?}",
+            name,
+            query,
+            line
+        );
+    }
+}
+
+fn matcher(patterns: &[&str], regex: bool) -> Matcher {
+    let config = Config {
+        patterns: patterns.iter().map(|p| p.to_string()).collect(),
+        case_sensitive: false,
+        regex,
+        ..Config::default()
+    };
+    Matcher::new(&config).unwrap()
+}
+
+#[test]
+fn search_insensitive_corpus() {
+    check("search_insensitive", |query, line| {
+        search_insensitive(query, line) == vec![line]
+    });
+}
+
+#[test]
+fn matcher_corpus() {
+    check("-i", |query, line| matcher(&[query], false).is_match(line));
+    // two patterns go through Aho-Corasick instead
+    check("-e", |query, line| {
+        query.is_empty() || matcher(&[query, "\u{0}"], false).is_match(line)
+    });
+    check("-E", |query, line| {
+        matcher(&[&regex::escape(query)], true).is_match(line)
+    });
+}
+
+#[test]
+fn highlights_the_original_text() {
+    let line = "GROẞE Straße, \u{212A}ELVIN";
+    let spans = matcher(&["straße", "kelvin"], false).find_spans(line);
+    let found: Vec<&str> = spans.iter().map(|span| &line[span.clone()]).collect();
+    assert_eq!(vec!["Straße", "\u{212A}ELVIN"], found);
+    let spans = matcher(&["große"], false).find_spans(line);
+    assert_eq!(vec!["GROẞE"], vec![&line[spans[0].clone()]]);
+}
@@ -0,0 +1,428 @@
+// integration tests, these run the real minigrep binary like a script would
+use std::process::{Command, Output};
+
+fn minigrep(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_minigrep"))
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .env_remove("CASE_INSENSITIVE")
+        .env("MINIGREP_CONFIG", "") // dont pick up the config file of whoever runs the tests
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn exit_codes_follow_grep() {
+    let found = minigrep(&["frog", "poem.txt"]);
+    assert_eq!(Some(0), found.status.code());
+    assert_eq!(
+        "How public, like a frog\nTo tell your name the livelong day frog\n",
+        String::from_utf8_lossy(&found.stdout)
+    );
+
+    assert_eq!(Some(1), minigrep(&["monkey", "poem.txt"]).status.code());
+    assert_eq!(Some(2), minigrep(&["frog", "missing.txt"]).status.code());
+    assert_eq!(
+        Some(2),
+        minigrep(&["-E", "(frog", "poem.txt"]).status.code()
+    );
+    assert_eq!(
+        Some(2),
+        minigrep(&["--no-such-flag", "frog", "poem.txt"])
+            .status
+            .code()
+    );
+}
+
+#[test]
+fn any_of_many_patterns_matches() {
+    let output = minigrep(&["-i", "-e", "frog", "-e", "nobody", "poem.txt"]);
+    assert_eq!(
+        "I'm nobody! Who are you?\n\
+         Are you nobody, too?\n\
+         How public, like a frog\n\
+         To tell your name the livelong day frog\n",
+        String::from_utf8_lossy(&output.stdout)
+    );
+}
+
+#[test]
+fn parallel_output_is_ordered() {
+    let root = std::env::temp_dir().join(format!("minigrep_cli_parallel_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    for i in 0..40 {
+        let dir = root.join(format!("d{}", i % 3));
+        std::fs::create_dir_all(&dir).unwrap();
+        let contents: String = (0..i * 20).map(|n| format!("{n} frog\n")).collect();
+        std::fs::write(dir.join(format!("f{i}.txt")), contents).unwrap();
+    }
+    let root_arg = root.to_str().unwrap();
+
+    let one = minigrep(&["-j", "1", "-n", "-C", "1", "7 frog", root_arg]);
+    let many = minigrep(&["-j", "8", "-n", "-C", "1", "7 frog", root_arg]);
+    assert_eq!(Some(0), many.status.code());
+    assert!(!one.stdout.is_empty());
+    assert_eq!(one.stdout, many.stdout);
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn smart_case_from_the_config_file() {
+    let file = std::env::temp_dir().join(format!("minigrep_cli_config_{}", std::process::id()));
+    std::fs::write(&file, "# defaults\n--smart-case\n").unwrap();
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_minigrep"))
+            .args(args)
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .env_remove("CASE_INSENSITIVE")
+            .env("MINIGREP_CONFIG", &file)
+            .output()
+            .unwrap()
+    };
+
+    // all lowercase: case insensitive
+    let output = run(&["-c", "to", "poem.txt"]);
+    assert_eq!("4\n", String::from_utf8_lossy(&output.stdout));
+    // an uppercase letter: case sensitive
+    let output = run(&["-c", "To", "poem.txt"]);
+    assert_eq!("2\n", String::from_utf8_lossy(&output.stdout));
+    // the command line still wins
+    let output = run(&["-c", "-s", "to", "poem.txt"]);
+    assert_eq!("2\n", String::from_utf8_lossy(&output.stdout));
+    let output = run(&["--no-config", "-c", "to", "poem.txt"]);
+    assert_eq!("2\n", String::from_utf8_lossy(&output.stdout));
+
+    std::fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn binary_and_latin1_files() {
+    let root = std::env::temp_dir().join(format!("minigrep_cli_binary_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(&root).unwrap();
+    std::fs::write(root.join("image.bin"), b"\x89PNG\x00\x00frog\x00\xff\n").unwrap();
+    std::fs::write(root.join("latin1.log"), b"caf\xe9 frog\nno match\n").unwrap();
+    let root_arg = root.to_str().unwrap();
+
+    let output = minigrep(&["-j", "1", "frog", root_arg]);
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!(
+        format!(
+            "Binary file {0}/image.bin matches\n{0}/latin1.log:caf� frog\n",
+            root_arg
+        ),
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    // -c still counts the lines of a binary file and -a prints them
+    let output = minigrep(&["-c", "frog", &format!("{root_arg}/image.bin")]);
+    assert_eq!("1\n", String::from_utf8_lossy(&output.stdout));
+    let output = minigrep(&["-a", "frog", &format!("{root_arg}/image.bin")]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("PNG"));
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn utf16_files_are_transcoded() {
+    let file = std::env::temp_dir().join(format!("minigrep_cli_utf16_{}.csv", std::process::id()));
+    let mut bytes = vec![0xFF, 0xFE];
+    bytes.extend(
+        "name;city\r\nJürgen;Köln\r\n"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes),
+    );
+    std::fs::write(&file, bytes).unwrap();
+
+    let output = minigrep(&["-n", "köln", "-i", file.to_str().unwrap()]);
+    assert_eq!("2:Jürgen;Köln\n", String::from_utf8_lossy(&output.stdout));
+
+    // -b counts the UTF-8 the file decodes to, not the UTF-16 bytes on disk
+    let output = minigrep(&["-b", "Köln", file.to_str().unwrap()]);
+    assert_eq!("11:Jürgen;Köln\n", String::from_utf8_lossy(&output.stdout));
+
+    // forced to Latin-1 the text is full of NUL bytes, so it looks binary
+    let output = minigrep(&[
+        "--encoding",
+        "latin-1",
+        "-c",
+        "name",
+        file.to_str().unwrap(),
+    ]);
+    assert_eq!("0\n", String::from_utf8_lossy(&output.stdout));
+
+    // --in-place writes it back as UTF-16 with its BOM
+    let output = minigrep(&["Köln", "-r", "Bonn", "--in-place", file.to_str().unwrap()]);
+    assert_eq!(Some(0), output.status.code());
+    let mut expected = vec![0xFF, 0xFE];
+    expected.extend(
+        "name;city\r\nJürgen;Bonn\r\n"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes),
+    );
+    assert_eq!(expected, std::fs::read(&file).unwrap());
+
+    // Latin-1 has no BOM, it is only written back when --encoding says what it is
+    std::fs::write(&file, b"K\xf6ln\n").unwrap();
+    let output = minigrep(&["-r", "Bonn", "--in-place", "ln", file.to_str().unwrap()]);
+    assert_eq!(Some(2), output.status.code());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("file is not valid UTF-8"));
+    let output = minigrep(&[
+        "--encoding",
+        "latin-1",
+        "-r",
+        "Bonn",
+        "--in-place",
+        "K.ln",
+        "-E",
+        file.to_str().unwrap(),
+    ]);
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!(b"Bonn\n".to_vec(), std::fs::read(&file).unwrap());
+
+    std::fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn replace_preview_and_in_place() {
+    let root = std::env::temp_dir().join(format!("minigrep_cli_replace_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(&root).unwrap();
+    let file = root.join("lib.rs");
+    std::fs::write(&file, "fn add_two(a: i32) -> i32 {\n    a + 2\n}\n").unwrap();
+    let file_arg = file.to_str().unwrap();
+
+    let output = minigrep(&["-E", r"(\w+)_two", "-r", "${1}_2", file_arg]);
+    assert_eq!(
+        "fn add_2(a: i32) -> i32 {\n",
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    let output = minigrep(&["add_two", "-r", "plus_two", "--dry-run", file_arg]);
+    assert_eq!(
+        format!(
+            "--- {0}\n+++ {0}\n@@ -1,3 +1,3 @@\n-fn add_two(a: i32) -> i32 {{\n\
+             +fn plus_two(a: i32) -> i32 {{\n     a + 2\n }}\n",
+            file_arg
+        ),
+        String::from_utf8_lossy(&output.stdout)
+    );
+    // a dry run does not touch the file
+    assert!(std::fs::read_to_string(&file).unwrap().contains("add_two"));
+
+    let output = minigrep(&[
+        "add_two",
+        "-r",
+        "plus_two",
+        "--in-place",
+        "--backup",
+        file_arg,
+    ]);
+    assert_eq!(Some(0), output.status.code());
+    assert!(output.stdout.is_empty());
+    assert!(std::fs::read_to_string(&file)
+        .unwrap()
+        .starts_with("fn plus_two("));
+    assert!(std::fs::read_to_string(root.join("lib.rs.bak"))
+        .unwrap()
+        .starts_with("fn add_two("));
+
+    // a binary file that matches is skipped, but not without a word
+    let binary = root.join("bin.dat");
+    std::fs::write(&binary, b"foo\0bar\n").unwrap();
+    for mode in ["--in-place", "--dry-run"] {
+        let output = minigrep(&["foo", "-r", "X", mode, binary.to_str().unwrap()]);
+        assert_eq!(Some(0), output.status.code());
+        assert!(output.stdout.is_empty());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("bin.dat matches"));
+        assert_eq!(b"foo\0bar\n".to_vec(), std::fs::read(&binary).unwrap());
+    }
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn fuzzy_shows_what_matched() {
+    let output = minigrep(&["-n", "--fuzzy", "1", "-i", "livelong", "poem.txt"]);
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!(
+        "8:To tell your name the livelong day frog\tlivelong (distance 0)\n",
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    let output = minigrep(&["--fuzzy", "2", "publik, lyke", "poem.txt"]);
+    assert_eq!(
+        "How public, like a frog\tpublic, like (distance 2)\n",
+        String::from_utf8_lossy(&output.stdout)
+    );
+}
+
+#[test]
+fn multiline_matches_print_every_line() {
+    // without -U a pattern never sees the line ending
+    let output = minigrep(&["-n", "-E", "frog\\nTo tell", "poem.txt"]);
+    assert_eq!(Some(1), output.status.code());
+
+    let output = minigrep(&["-n", "-U", "-E", "frog\\nTo tell", "poem.txt"]);
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!(
+        "7:How public, like a frog\n8:To tell your name the livelong day frog\n",
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    // --json says where the match ends too, and counts both of its lines
+    let output = minigrep(&["--json", "-U", "-E", "frog\\nTo tell", "poem.txt"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert!(lines[1].contains(r#""line_number":7,"last_line_number":8,"#));
+    assert!(lines[2].contains(r#""stats":{"matched_lines":2,"matches":1}"#));
+    assert!(lines[3].contains(r#""matched_lines":2}"#));
+}
+
+#[test]
+fn json_lines() {
+    let output = minigrep(&["--json", "frog", "poem.txt", "missing.txt"]);
+    // missing.txt is still an error, but the summary is written first
+    assert_eq!(Some(2), output.status.code());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(5, lines.len());
+    assert_eq!(
+        r#"{"type":"begin","data":{"path":{"text":"poem.txt"}}}"#,
+        lines[0]
+    );
+    assert!(lines[1].starts_with(
+        r#"{"type":"match","data":{"path":{"text":"poem.txt"},"lines":{"text":"How public, like a frog"},"line_number":7,"#
+    ));
+    assert!(lines[3].contains(r#""stats":{"matched_lines":2,"matches":2}"#));
+    assert!(lines[4].starts_with(
+        r#"{"type":"summary","data":{"stats":{"searches":1,"searches_with_match":1,"matched_lines":2},"elapsed_secs":"#
+    ));
+}
+
+#[test]
+fn boolean_queries() {
+    let output = minigrep(&["-n", "--query", "frog AND NOT public", "poem.txt"]);
+    assert_eq!(
+        "8:To tell your name the livelong day frog\n",
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    // nobody is on line 2 and frog on line 7, at file scope only the name is printed
+    let output = minigrep(&["--query-scope=file", "nobody NEAR/5 frog", "poem.txt"]);
+    assert_eq!("poem.txt\n", String::from_utf8_lossy(&output.stdout));
+    let output = minigrep(&["--query-scope=file", "nobody NEAR/4 frog", "poem.txt"]);
+    assert_eq!(Some(1), output.status.code());
+
+    let output = minigrep(&["--query", "frog OR (bog", "poem.txt"]);
+    assert_eq!(Some(2), output.status.code());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("this ( is never closed at column 9"));
+}
+
+#[test]
+fn index_skips_files_that_can_not_match() {
+    let dir = std::env::temp_dir().join(format!("minigrep_cli_index_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("a.txt"), "alpha\n").unwrap();
+    std::fs::write(dir.join("b.txt"), "gamma\n").unwrap();
+    let dir_arg = dir.to_str().unwrap();
+
+    let output = minigrep(&["index", dir_arg]);
+    assert_eq!(Some(0), output.status.code());
+    assert!(String::from_utf8_lossy(&output.stdout)
+        .ends_with(": 2 files, 2 new or changed, 0 removed\n"));
+
+    // same size and mtime, so the index still trusts what it knows about b.txt and never opens it
+    let b = dir.join("b.txt");
+    let mtime = std::fs::metadata(&b).unwrap().modified().unwrap();
+    std::fs::write(&b, "alpha\n").unwrap();
+    std::fs::File::options()
+        .write(true)
+        .open(&b)
+        .unwrap()
+        .set_modified(mtime)
+        .unwrap();
+
+    let output = minigrep(&["search", "--index", "-c", "alpha", dir_arg]);
+    let b_name = b.display().to_string();
+    assert!(String::from_utf8_lossy(&output.stdout).contains(&format!("{b_name}:0\n")));
+    // without the index it is searched like always
+    let output = minigrep(&["-c", "alpha", dir_arg]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains(&format!("{b_name}:1\n")));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn watch_prints_appended_lines() {
+    use std::io::{BufRead, BufReader, Write};
+    use std::process::Stdio;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    let dir = std::env::temp_dir().join(format!("minigrep_cli_watch_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let log = dir.join("app.log");
+    std::fs::write(&log, "error one\nok\n").unwrap();
+    let utf16 =
+        |text: &str| -> Vec<u8> { text.encode_utf16().flat_map(u16::to_le_bytes).collect() };
+    let wide = dir.join("wide.log");
+    std::fs::write(&wide, [&[0xFF, 0xFE][..], &utf16("error wide\n")].concat()).unwrap();
+
+    let missing = dir.join("missing.log");
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_minigrep"))
+        .args(["--watch", "-n", "-B1", "error"])
+        .args([&log, &wide, &missing])
+        .env("MINIGREP_CONFIG", "")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // the lines come in while minigrep keeps running, so they are read on another thread
+    let (sender, lines) = mpsc::channel();
+    let stdout = child.stdout.take().unwrap();
+    std::thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            if sender.send(line.unwrap()).is_err() {
+                break;
+            }
+        }
+    });
+    // the -- between groups is left out, the line numbers show the gap too
+    let next = || loop {
+        let line = lines.recv_timeout(Duration::from_secs(10)).unwrap();
+        if line != "--" {
+            return line;
+        }
+    };
+    let name = log.display().to_string();
+    let wide_name = wide.display().to_string();
+
+    assert_eq!(format!("{name}:1:error one"), next());
+    assert_eq!(format!("{wide_name}:1:error wide"), next());
+    let mut file = std::fs::File::options().append(true).open(&log).unwrap();
+    file.write_all(b"ok\nerror two\n").unwrap();
+    // the line before is an old one, it still comes as -B context
+    assert_eq!(format!("{name}-3-ok"), next());
+    assert_eq!(format!("{name}:4:error two"), next());
+    // rewritten in place, all of its matches are printed again
+    std::fs::write(&log, "error three\n").unwrap();
+    assert_eq!(format!("{name}:1:error three"), next());
+    // a UTF-16 file is appended to the same way
+    let mut file = std::fs::File::options().append(true).open(&wide).unwrap();
+    file.write_all(&utf16("fine\nerror again\n")).unwrap();
+    assert_eq!(format!("{wide_name}-2-fine"), next());
+    assert_eq!(format!("{wide_name}:3:error again"), next());
+
+    child.kill().unwrap();
+    let output = child.wait_with_output().unwrap();
+    // a path that is not there is reported once, and not every time minigrep looks
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(1, stderr.matches("missing.log").count());
+    assert!(!stderr.contains("wide.log: rewritten"), "{stderr}");
+    std::fs::remove_dir_all(&dir).unwrap();
+}